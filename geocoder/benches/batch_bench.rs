@@ -0,0 +1,35 @@
+extern crate criterion;
+extern crate geocoder;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use geocoder::{Float, ReverseGeocoder};
+use rand::Rng;
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let gc = ReverseGeocoder::from_file("../cities.txt");
+    let mut rng = rand::thread_rng();
+    let points: Vec<(Float, Float)> = (0..10_000)
+        .map(|_| (rng.gen_range(-90.0..90.0), rng.gen_range(-180.0..180.0)))
+        .collect();
+
+    let mut group = c.benchmark_group("10000 points");
+    group.throughput(Throughput::Elements(points.len() as u64));
+    for results in [1, 5] {
+        group.bench_with_input(BenchmarkId::new("search", results), &results, |b, &k| {
+            b.iter(|| {
+                for (lat, lng) in &points {
+                    black_box(gc.search(*lat, *lng, k));
+                }
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("search_batch", results),
+            &results,
+            |b, &k| b.iter(|| gc.search_batch(black_box(&points), k)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,139 @@
+//! Reports the heap memory used by the cities of a data file.
+//!
+//! Compares the former layout, with a `String` per column, to [`City`] and the whole
+//! [`ReverseGeocoder`]. Uses `../cities.txt` unless `GEOCODER_BENCH_DATA_FILE` is set, e.g.
+//!
+//!     GEOCODER_BENCH_DATA_FILE=cities500.txt cargo bench --bench memory_bench
+extern crate geocoder;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use geocoder::{City, Float, LoadOptions, ReverseGeocoder};
+use serde::Deserialize;
+
+/// Counts the bytes currently allocated on the heap.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Layout of a city before codes were interned and names packed.
+#[allow(dead_code)]
+#[derive(Deserialize)]
+struct StringCity {
+    id: u32,
+    name: String,
+    asciiname: String,
+    alternatenames: String,
+    latitude: Float,
+    longitude: Float,
+    feature_class: String,
+    feature_code: String,
+    country_code: String,
+    cc2: String,
+    admin1_code: String,
+    admin2_code: String,
+    admin3_code: String,
+    admin4_code: String,
+    population: Option<u32>,
+    elevation: Option<i16>,
+    dem: String,
+    timezone: String,
+    modification_date: String,
+}
+
+/// Heap bytes still allocated by the value returned from `f`.
+fn measure<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let value = f();
+    let after = ALLOCATED.load(Ordering::Relaxed);
+    (value, after.saturating_sub(before))
+}
+
+fn read<T: for<'de> Deserialize<'de>>(filename: &str) -> Vec<T> {
+    let mut rows: Vec<T> = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b'\t')
+        .from_path(filename)
+        .expect("data file can't be read")
+        .deserialize()
+        .collect::<Result<_, _>>()
+        .expect("data file can't be parsed");
+    rows.shrink_to_fit();
+    rows
+}
+
+fn report(label: &str, bytes: usize, cities: usize) {
+    println!(
+        "{:<48} {:>8.1} MiB {:>8} bytes/city",
+        label,
+        bytes as f64 / (1024.0 * 1024.0),
+        bytes / cities.max(1)
+    );
+}
+
+fn main() {
+    let filename =
+        std::env::var("GEOCODER_BENCH_DATA_FILE").unwrap_or_else(|_| String::from("../cities.txt"));
+    println!("Memory used by the cities of {}", filename);
+
+    let (before, bytes) = measure(|| read::<StringCity>(&filename));
+    let cities = before.len();
+    report("String columns (before)", bytes, cities);
+    drop(before);
+
+    // The first read also interns the codes, which are kept for the whole process
+    let (after, with_codes) = measure(|| read::<City>(&filename));
+    drop(after);
+    let (after, bytes) = measure(|| read::<City>(&filename));
+    report("City (after)", bytes, cities);
+    report(
+        "  + interned codes, once per process",
+        with_codes - bytes,
+        cities,
+    );
+    drop(after);
+
+    let (dropped, bytes) = measure(|| {
+        let mut cities = read::<City>(&filename);
+        for city in cities.iter_mut() {
+            city.names.drop_alternatenames();
+            city.dem = Box::default();
+        }
+        cities
+    });
+    report("City without alternatenames and dem", bytes, cities);
+    drop(dropped);
+
+    let (gc, bytes) = measure(|| ReverseGeocoder::from_file(&filename));
+    report("ReverseGeocoder", bytes, cities);
+    drop(gc);
+
+    let options = LoadOptions {
+        drop_alternatenames: true,
+        drop_dem: true,
+        ..Default::default()
+    };
+    let (gc, bytes) = measure(|| ReverseGeocoder::from_file_with_options(&filename, &options));
+    report(
+        "ReverseGeocoder without alternatenames and dem",
+        bytes,
+        cities,
+    );
+    drop(gc);
+}
@@ -0,0 +1,31 @@
+//! Convert a GeoNames data file into a binary snapshot, or a memory-mapped snapshot with `--mmap`.
+//!
+//!     cargo run --release --example snapshot -- cities500.txt cities500.snapshot
+//!     cargo run --release --example snapshot -- --mmap allCountries.txt allCountries.mmap
+
+use std::env;
+use std::process::exit;
+
+use geocoder::ReverseGeocoder;
+
+fn main() {
+    let mut args: Vec<String> = env::args().collect();
+    let mapped = args.iter().any(|arg| arg == "--mmap");
+    args.retain(|arg| arg != "--mmap");
+    let [_, input, output] = args.as_slice() else {
+        eprintln!("Usage: {} [--mmap] <data file> <snapshot file>", args[0]);
+        exit(2);
+    };
+
+    let result = ReverseGeocoder::try_from_file(input).and_then(|gc| {
+        if mapped {
+            gc.save_mapped(output)
+        } else {
+            gc.save_snapshot(output)
+        }
+    });
+    if let Err(e) = result {
+        eprintln!("Unable to create snapshot: {}", e);
+        exit(1);
+    }
+}
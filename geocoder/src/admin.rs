@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{errors, parse_csv_file, City, ReverseGeocoder};
+
+/// Column names of the admin1CodesASCII.txt/admin2Codes.txt exports, used in error messages.
+const ADMIN_CODE_COLUMNS: [&str; 4] = ["code", "name", "asciiname", "geonameid"];
+
+/// Administrative division, as defined in the http://www.geonames.org exports
+/// `admin1CodesASCII.txt` and `admin2Codes.txt`.
+///
+/// # Examples
+/// ```rust
+/// let division = geocoder::AdminDivision {
+///     code: String::from("DE.07"),
+///     name: String::from("North Rhine-Westphalia"),
+///     asciiname: String::from("North Rhine-Westphalia"),
+///     id: 2861876,
+/// };
+/// ```
+#[rustfmt::skip]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AdminDivision {
+    pub code: String,       // concatenated codes, e.g. "DE.07" for admin1 or "DE.07.053" for admin2
+    pub name: String,       // name of the division (utf8)
+    pub asciiname: String,  // name of the division in plain ascii characters
+    pub id: u32,            // geonameid of the division
+}
+
+impl Display for AdminDivision {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Lookup table of administrative divisions by their concatenated code.
+pub(crate) type AdminDivisions = HashMap<String, AdminDivision>;
+
+fn parse_admin_codes(filename: &str) -> errors::Result<AdminDivisions> {
+    let (divisions, _) =
+        parse_csv_file::<AdminDivision, _>(filename, &ADMIN_CODE_COLUMNS, false, |_| true)?;
+    tracing::info!("Loaded {} administrative divisions", divisions.len());
+    Ok(divisions
+        .into_iter()
+        .map(|division| (division.code.clone(), division))
+        .collect())
+}
+
+fn admin1_key(city: &City) -> String {
+    format!("{}.{}", city.country_code, city.admin1_code)
+}
+
+fn admin2_key(city: &City) -> String {
+    format!(
+        "{}.{}.{}",
+        city.country_code, city.admin1_code, city.admin2_code
+    )
+}
+
+impl ReverseGeocoder {
+    /// Load first-level administrative divisions from a GeoNames `admin1CodesASCII.txt`.
+    ///
+    /// Replaces previously loaded admin1 divisions.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// let mut gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// gc.load_admin1_codes("admin1CodesASCII.txt")?;
+    /// # Ok::<(), geocoder::errors::Error>(())
+    /// ```
+    pub fn load_admin1_codes(&mut self, filename: &str) -> errors::Result<()> {
+        self.admin1 = parse_admin_codes(filename)?;
+        Ok(())
+    }
+
+    /// Load second-level administrative divisions from a GeoNames `admin2Codes.txt`.
+    ///
+    /// Replaces previously loaded admin2 divisions.
+    pub fn load_admin2_codes(&mut self, filename: &str) -> errors::Result<()> {
+        self.admin2 = parse_admin_codes(filename)?;
+        Ok(())
+    }
+
+    /// Resolve the first-level administrative division (e.g. a state) of the given city.
+    ///
+    /// Returns `None` if no admin1 codes are loaded or the code is unknown.
+    pub fn admin1(&self, city: &City) -> Option<&AdminDivision> {
+        if city.admin1_code.is_empty() {
+            return None;
+        }
+        self.admin1.get(&admin1_key(city))
+    }
+
+    /// Resolve the second-level administrative division (e.g. a county) of the given city.
+    ///
+    /// Returns `None` if no admin2 codes are loaded or the code is unknown.
+    pub fn admin2(&self, city: &City) -> Option<&AdminDivision> {
+        if city.admin2_code.is_empty() {
+            return None;
+        }
+        self.admin2.get(&admin2_key(city))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::write_test_file;
+    use tracing_test::traced_test;
+
+    #[test]
+    #[traced_test]
+    fn resolves_admin_names() {
+        let admin1 = write_test_file(
+            "admin1.txt",
+            "DE.07\tNorth Rhine-Westphalia\tNorth Rhine-Westphalia\t2861876\n\
+             DE.02\tBavaria\tBavaria\t2951839\n",
+        );
+        let admin2 = write_test_file(
+            "admin2.txt",
+            "DE.07.053\tRegierungsbezirk Köln\tRegierungsbezirk Koln\t2886240\n",
+        );
+        let mut gc = ReverseGeocoder::from_file("../cities.txt");
+        gc.load_admin1_codes(&admin1).unwrap();
+        gc.load_admin2_codes(&admin2).unwrap();
+
+        let (_, city) = gc.search(51.08, 6.31, 1)[0];
+        assert_eq!(gc.admin1(city).unwrap().name, "North Rhine-Westphalia");
+        assert_eq!(gc.admin1(city).unwrap().id, 2861876);
+        assert_eq!(gc.admin2(city).unwrap().name, "Regierungsbezirk Köln");
+
+        let (_, city) = gc.search(35.69, 139.69, 1)[0];
+        assert_eq!(gc.admin1(city), None);
+        assert_eq!(gc.admin2(city), None);
+    }
+}
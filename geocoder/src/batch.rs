@@ -0,0 +1,160 @@
+use kiddo::float::distance::squared_euclidean;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{degrees_lat_lng_to_unit_sphere, Float, Locatable, ReverseGeocoder};
+
+/// Nearest cities of many points, see [`ReverseGeocoder::search_batch`].
+///
+/// Stores the same number of results for every point in two flat vectors instead of a Vec per
+/// point. Results are indices into [`ReverseGeocoder::cities`] with their distance in
+/// metres, which is stored as f32 and therefore precise to about a metre for distant places.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchResults {
+    points: usize,
+    per_point: usize,
+    indices: Vec<u32>,
+    distances: Vec<f32>,
+}
+
+impl BatchResults {
+    /// Number of query points.
+    pub fn len(&self) -> usize {
+        self.points
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points == 0
+    }
+
+    /// Indices of the cities nearest to the `point`-th query point, nearest first.
+    ///
+    /// # Panics
+    /// Panics if `point` is out of bounds.
+    pub fn indices(&self, point: usize) -> &[u32] {
+        assert!(point < self.points, "point {} out of bounds", point);
+        &self.indices[point * self.per_point..(point + 1) * self.per_point]
+    }
+
+    /// Distances in metres of the cities returned by [`BatchResults::indices`].
+    ///
+    /// # Panics
+    /// Panics if `point` is out of bounds.
+    pub fn distances(&self, point: usize) -> &[f32] {
+        assert!(point < self.points, "point {} out of bounds", point);
+        &self.distances[point * self.per_point..(point + 1) * self.per_point]
+    }
+}
+
+impl<T: Locatable + Sync> ReverseGeocoder<T> {
+    /// Finds the `results` cities nearest to each of the given `(lat, lng)` points.
+    ///
+    /// Like [`ReverseGeocoder::search`], but for many points at once, with the results of all
+    /// points stored in two flat vectors. Searching for the single nearest city doesn't allocate
+    /// per point, for more results the tree still allocates a small heap per point. With the
+    /// `rayon` feature, the points are searched on all cores.
+    ///
+    /// # Example
+    /// ```rust
+    /// let gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// let found = gc.search_batch(&[(51.08, 6.31), (35.68, 139.76)], 1);
+    /// let erkelenz = &gc.cities()[found.indices(0)[0] as usize];
+    /// assert_eq!(erkelenz.name(), "Erkelenz");
+    /// assert!(found.distances(1)[0] < 10_000.0);
+    /// ```
+    pub fn search_batch(&self, points: &[(Float, Float)], results: usize) -> BatchResults {
+        let per_point = results.min(self.cities.len());
+        let mut found = BatchResults {
+            points: points.len(),
+            per_point,
+            indices: vec![0; points.len() * per_point],
+            distances: vec![0.0; points.len() * per_point],
+        };
+        if per_point == 0 {
+            return found;
+        }
+
+        #[cfg(feature = "rayon")]
+        found
+            .indices
+            .par_chunks_mut(per_point)
+            .zip(found.distances.par_chunks_mut(per_point))
+            .zip(points.par_iter())
+            .for_each(|((indices, distances), point)| self.search_into(*point, indices, distances));
+        #[cfg(not(feature = "rayon"))]
+        found
+            .indices
+            .chunks_mut(per_point)
+            .zip(found.distances.chunks_mut(per_point))
+            .zip(points.iter())
+            .for_each(|((indices, distances), point)| self.search_into(*point, indices, distances));
+        found
+    }
+
+    /// Search the nearest cities of a point, filling all of `indices` and `distances`.
+    #[allow(clippy::unnecessary_cast)] // Float is f64 with the "f64" feature
+    fn search_into(&self, (lat, lng): (Float, Float), indices: &mut [u32], distances: &mut [f32]) {
+        let query = degrees_lat_lng_to_unit_sphere(lat, lng);
+        if indices.len() == 1 {
+            // Doesn't allocate, unlike nearest_n
+            let (_, idx) = self.tree.nearest_one(&query, &squared_euclidean);
+            indices[0] = idx as u32;
+        } else {
+            let neighbours = self
+                .tree
+                .nearest_n(&query, indices.len(), &squared_euclidean);
+            for (index, neighbour) in indices.iter_mut().zip(neighbours) {
+                *index = neighbour.item as u32;
+            }
+        }
+        for (index, distance) in indices.iter().zip(distances.iter_mut()) {
+            let (city_lat, city_lng) = self.cities[*index as usize].coordinates();
+            *distance = self.distance_method.distance(
+                lat as f64,
+                lng as f64,
+                city_lat as f64,
+                city_lng as f64,
+            ) as f32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_test::traced_test;
+
+    #[test]
+    #[traced_test]
+    fn matches_single_searches() {
+        let gc = ReverseGeocoder::from_file("../cities.txt");
+        let points = [(51.08, 6.31), (47.1, 11.0), (-23.0, -46.0), (35.0, 135.0)];
+
+        for results in [1, 3] {
+            let found = gc.search_batch(&points, results);
+            assert_eq!(found.len(), points.len());
+            for (point, (lat, lng)) in points.iter().enumerate() {
+                let expected = gc.search(*lat, *lng, results);
+                assert_eq!(found.indices(point).len(), results);
+                for (i, (distance, city)) in expected.iter().enumerate() {
+                    let idx = found.indices(point)[i] as usize;
+                    assert_eq!(gc.cities()[idx].id, city.id);
+                    assert_eq!(found.distances(point)[i], *distance as f32);
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[traced_test]
+    fn limits_results_to_available_cities() {
+        let gc = ReverseGeocoder::from_file("../cities.txt");
+        let found = gc.search_batch(&[(0.0, 0.0)], 100);
+        assert_eq!(found.indices(0).len(), 11);
+
+        let empty: ReverseGeocoder = ReverseGeocoder::new(vec![]);
+        let found = empty.search_batch(&[(0.0, 0.0)], 1);
+        assert_eq!((found.len(), found.indices(0)), (1, &[][..]));
+        assert!(gc.search_batch(&[], 1).is_empty());
+    }
+}
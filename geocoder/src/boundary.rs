@@ -0,0 +1,362 @@
+use std::fmt::{Display, Formatter};
+
+use geojson::{GeoJson, JsonObject, JsonValue, Value};
+use rstar::primitives::{GeomWithData, Rectangle};
+use rstar::RTree;
+
+use crate::errors::Error;
+use crate::{errors, read_data_file, to_f64, Float, Locatable, ReverseGeocoder};
+
+/// Feature properties holding the code of a boundary, the first present one is used. Covers
+/// Natural Earth (`ISO_A2_EH` is also set for France and Norway), geoBoundaries and own files.
+const CODE_PROPERTIES: [&str; 6] = [
+    "ISO_A2_EH",
+    "ISO_A2",
+    "iso_a2",
+    "iso_3166_2",
+    "shapeISO",
+    "code",
+];
+
+/// Feature properties holding the name of a boundary, the first present one is used.
+const NAME_PROPERTIES: [&str; 4] = ["NAME", "name", "shapeName", "ADMIN"];
+
+/// Ring of a polygon as `[lng, lat]` positions, the first ring of a polygon is its exterior.
+type Ring = Vec<[f64; 2]>;
+
+/// Area like a country or state, read from a GeoJSON feature.
+///
+/// Its polygons are only used for lookups by [`Boundaries::containing`].
+#[derive(Clone, Default, PartialEq)]
+pub struct Boundary {
+    /// Code of the area, e.g. the ISO country code
+    pub code: String,
+    /// Name of the area
+    pub name: String,
+    polygons: Vec<Vec<Ring>>,
+}
+
+impl Boundary {
+    /// Returns `true` if the point is within one of the polygons and not in one of its holes.
+    pub fn contains(&self, lat: Float, lng: Float) -> bool {
+        let (lat, lng) = (to_f64(lat), to_f64(lng));
+        self.polygons
+            .iter()
+            .any(|rings| polygon_contains(rings, lat, lng))
+    }
+}
+
+impl std::fmt::Debug for Boundary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Boundary")
+            .field("code", &self.code)
+            .field("name", &self.name)
+            .field("polygons", &self.polygons.len())
+            .finish()
+    }
+}
+
+impl Display for Boundary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Even-odd rule over all rings, so points in holes are outside.
+fn polygon_contains(rings: &[Ring], lat: f64, lng: f64) -> bool {
+    let mut inside = false;
+    for ring in rings {
+        // Rings are closed in GeoJSON, the edge back to the first position is only a safeguard
+        for (a, b) in ring.iter().zip(ring.iter().skip(1).chain(ring.first())) {
+            if (a[1] > lat) != (b[1] > lat)
+                && lng < (b[0] - a[0]) * (lat - a[1]) / (b[1] - a[1]) + a[0]
+            {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+/// Envelope of a polygon in the R-tree, with the index of its boundary and polygon.
+type Envelope = GeomWithData<Rectangle<[f64; 2]>, (usize, usize)>;
+
+/// Boundaries of countries or administrative divisions, indexed in an R-tree to find the one
+/// containing a point.
+///
+/// Unlike the nearest city, this is correct close to borders as well. Boundaries loaded into a
+/// [`ReverseGeocoder`] aren't part of its snapshots, and loading a layer again replaces the
+/// boundaries loaded before.
+///
+/// # Example
+/// ```rust,no_run
+/// let countries = geocoder::Boundaries::from_file("ne_10m_admin_0_countries.geojson")?;
+/// let country = countries.containing(47.56, 7.59).map(|country| &country.code);
+/// # Ok::<(), geocoder::errors::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct Boundaries {
+    boundaries: Vec<Boundary>,
+    tree: RTree<Envelope>,
+}
+
+impl Boundaries {
+    /// Read the polygon and multipolygon features of a GeoJSON file, which may also be zipped or
+    /// gzipped. Other features are skipped.
+    ///
+    /// The code and name are read from well-known properties of common boundary datasets, e.g.
+    /// `ISO_A2` and `NAME` of Natural Earth or `shapeISO` and `shapeName` of geoBoundaries,
+    /// otherwise from `code` and `name`.
+    pub fn from_file(filename: &str) -> errors::Result<Boundaries> {
+        Self::from_file_with_properties(filename, &CODE_PROPERTIES, &NAME_PROPERTIES)
+    }
+
+    /// Like [`Boundaries::from_file`], reading the code and name from the first present of the
+    /// given properties.
+    pub fn from_file_with_properties<S: AsRef<str>>(
+        filename: &str,
+        code_properties: &[S],
+        name_properties: &[S],
+    ) -> errors::Result<Boundaries> {
+        let geojson = read_data_file(filename, |data| {
+            GeoJson::from_reader(data).map_err(|e| invalid_boundaries(filename, e))
+        })?;
+        let features = match geojson {
+            GeoJson::FeatureCollection(collection) => collection.features,
+            GeoJson::Feature(feature) => vec![feature],
+            GeoJson::Geometry(_) => {
+                return Err(invalid_boundaries(filename, "expected features"));
+            }
+        };
+
+        let mut boundaries = vec![];
+        for feature in features {
+            let polygons = match feature.geometry.map(|geometry| geometry.value) {
+                Some(Value::Polygon(polygon)) => vec![to_rings(polygon)],
+                Some(Value::MultiPolygon(polygons)) => polygons.into_iter().map(to_rings).collect(),
+                _ => continue,
+            };
+            let properties = feature.properties.unwrap_or_default();
+            boundaries.push(Boundary {
+                code: first_property(&properties, code_properties),
+                name: first_property(&properties, name_properties),
+                polygons,
+            });
+        }
+        tracing::info!("Loaded {} boundaries from {}", boundaries.len(), filename);
+        Ok(Self::new(boundaries))
+    }
+
+    fn new(boundaries: Vec<Boundary>) -> Boundaries {
+        let envelopes = boundaries
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, boundary)| {
+                boundary
+                    .polygons
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(polygon, rings)| {
+                        Some(Envelope::new(envelope(rings.first()?)?, (idx, polygon)))
+                    })
+            })
+            .collect();
+        Boundaries {
+            boundaries,
+            tree: RTree::bulk_load(envelopes),
+        }
+    }
+
+    /// The boundary containing the given coordinates. If boundaries overlap, the first one in
+    /// the file wins.
+    pub fn containing(&self, lat: Float, lng: Float) -> Option<&Boundary> {
+        let (lat, lng) = (to_f64(lat), to_f64(lng));
+        self.tree
+            .locate_all_at_point(&[lng, lat])
+            .map(|envelope| envelope.data)
+            .filter(|(idx, polygon)| {
+                polygon_contains(&self.boundaries[*idx].polygons[*polygon], lat, lng)
+            })
+            .map(|(idx, _)| idx)
+            .min()
+            .map(|idx| &self.boundaries[idx])
+    }
+
+    pub fn len(&self) -> usize {
+        self.boundaries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boundaries.is_empty()
+    }
+}
+
+fn to_rings(polygon: Vec<Vec<Vec<f64>>>) -> Vec<Ring> {
+    polygon
+        .into_iter()
+        .map(|ring| {
+            ring.into_iter()
+                .filter_map(|position| Some([*position.first()?, *position.get(1)?]))
+                .collect()
+        })
+        .collect()
+}
+
+/// Bounding box of a ring, `None` if it's empty.
+fn envelope(ring: &Ring) -> Option<Rectangle<[f64; 2]>> {
+    let first = ring.first()?;
+    let (min, max) = ring.iter().fold((*first, *first), |(min, max), p| {
+        (
+            [min[0].min(p[0]), min[1].min(p[1])],
+            [max[0].max(p[0]), max[1].max(p[1])],
+        )
+    });
+    Some(Rectangle::from_corners(min, max))
+}
+
+/// Value of the first present property, numbers are converted to strings.
+fn first_property<S: AsRef<str>>(properties: &JsonObject, keys: &[S]) -> String {
+    keys.iter()
+        .find_map(|key| match properties.get(key.as_ref()) {
+            Some(JsonValue::String(value)) => Some(value.clone()),
+            Some(JsonValue::Number(value)) => Some(value.to_string()),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+fn invalid_boundaries<E: ToString>(filename: &str, reason: E) -> Error {
+    Error::InvalidBoundaries {
+        file: filename.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+impl<T: Locatable> ReverseGeocoder<T> {
+    /// Load country boundaries from a GeoJSON file, see [`Boundaries`] and
+    /// [`Boundaries::from_file`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// let mut gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// gc.load_country_boundaries("ne_10m_admin_0_countries.geojson")?;
+    /// let country = gc.country_boundaries().containing(47.56, 7.59);
+    /// # Ok::<(), geocoder::errors::Error>(())
+    /// ```
+    pub fn load_country_boundaries(&mut self, filename: &str) -> errors::Result<()> {
+        self.country_boundaries = Boundaries::from_file(filename)?;
+        Ok(())
+    }
+
+    /// Load first-level administrative boundaries (e.g. states) from a GeoJSON file, see
+    /// [`Boundaries`] and [`Boundaries::from_file`].
+    pub fn load_admin1_boundaries(&mut self, filename: &str) -> errors::Result<()> {
+        self.admin1_boundaries = Boundaries::from_file(filename)?;
+        Ok(())
+    }
+
+    /// Loaded country boundaries, empty by default.
+    pub fn country_boundaries(&self) -> &Boundaries {
+        &self.country_boundaries
+    }
+
+    /// Loaded first-level administrative boundaries, empty by default.
+    pub fn admin1_boundaries(&self) -> &Boundaries {
+        &self.admin1_boundaries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::write_test_file;
+    use tracing_test::traced_test;
+
+    /// Closed ring of a rectangle.
+    fn rectangle(min_lng: f64, min_lat: f64, max_lng: f64, max_lat: f64) -> String {
+        format!(
+            "[[{min_lng},{min_lat}],[{max_lng},{min_lat}],[{max_lng},{max_lat}],[{min_lng},{max_lat}],[{min_lng},{min_lat}]]"
+        )
+    }
+
+    fn feature(properties: &str, geometry: &str) -> String {
+        format!(r#"{{"type":"Feature","properties":{properties},"geometry":{geometry}}}"#)
+    }
+
+    fn boundaries() -> String {
+        let features = [
+            feature(
+                r#"{"ISO_A2":"CH","NAME":"Switzerland"}"#,
+                &format!(
+                    r#"{{"type":"Polygon","coordinates":[{}]}}"#,
+                    rectangle(7.5, 47.4, 7.7, 47.56)
+                ),
+            ),
+            // Germany with a hole for the enclave, and a second polygon further east
+            feature(
+                r#"{"ISO_A2":"DE","NAME":"Germany"}"#,
+                &format!(
+                    r#"{{"type":"MultiPolygon","coordinates":[[{},{}],[{}]]}}"#,
+                    rectangle(7.5, 47.56, 7.7, 47.7),
+                    rectangle(7.64, 47.64, 7.66, 47.66),
+                    rectangle(8.0, 47.56, 8.2, 47.7)
+                ),
+            ),
+            feature(
+                r#"{"code":"EN","name":"Enclave"}"#,
+                &format!(
+                    r#"{{"type":"Polygon","coordinates":[{}]}}"#,
+                    rectangle(7.64, 47.64, 7.66, 47.66)
+                ),
+            ),
+            feature(
+                r#"{"code":"PT","name":"Point"}"#,
+                r#"{"type":"Point","coordinates":[7.6,47.5]}"#,
+            ),
+        ];
+        write_test_file(
+            "boundaries.geojson",
+            &format!(
+                r#"{{"type":"FeatureCollection","features":[{}]}}"#,
+                features.join(",")
+            ),
+        )
+    }
+
+    #[test]
+    #[traced_test]
+    fn finds_boundary_containing_point() {
+        let boundaries = Boundaries::from_file(&boundaries()).unwrap();
+        assert_eq!(boundaries.len(), 3);
+
+        let code = |lat, lng| boundaries.containing(lat, lng).map(|b| b.code.as_str());
+        assert_eq!(code(47.55, 7.59), Some("CH"));
+        assert_eq!(code(47.57, 7.59), Some("DE"));
+        assert_eq!(code(47.6, 8.1), Some("DE"));
+        assert_eq!(code(47.65, 7.65), Some("EN"));
+        assert_eq!(code(47.6, 7.9), None);
+        assert_eq!(boundaries.containing(47.57, 7.59).unwrap().name, "Germany");
+    }
+
+    #[test]
+    #[traced_test]
+    fn loads_boundaries_into_geocoder() {
+        let mut gc = ReverseGeocoder::from_file("../cities.txt");
+        assert!(gc.country_boundaries().is_empty());
+        gc.load_country_boundaries(&boundaries()).unwrap();
+        assert_eq!(
+            gc.country_boundaries()
+                .containing(47.55, 7.59)
+                .unwrap()
+                .name,
+            "Switzerland"
+        );
+        assert!(gc.admin1_boundaries().is_empty());
+
+        let file = write_test_file("invalid.geojson", r#"{"type":"Point"}"#);
+        let error = gc.load_admin1_boundaries(&file).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("invalid.geojson: invalid boundaries"));
+    }
+}
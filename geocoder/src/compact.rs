@@ -0,0 +1,415 @@
+//! Compact building blocks of [`City`](crate::City).
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::{Arc, PoisonError, RwLock};
+
+use once_cell::sync::OnceCell;
+use serde::de::Visitor;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Number of codes in the first chunk, chunk `n` holds `FIRST_CHUNK << n` codes.
+const FIRST_CHUNK: usize = 64;
+/// Enough chunks for `u32::MAX` codes.
+const CHUNK_COUNT: usize = 27;
+
+type Chunk = OnceCell<Box<[OnceCell<&'static str>]>>;
+
+#[allow(clippy::declare_interior_mutable_const)] // Only used to initialize CHUNKS
+const EMPTY_CHUNK: Chunk = OnceCell::new();
+
+/// All interned codes, in chunks that never move once allocated, so codes are read without a
+/// lock.
+///
+/// Codes are never removed, they are shared by all geocoders and reloads. Only fields with few
+/// distinct values in a GeoNames export are codes, e.g. countries, feature codes and timezones,
+/// so this stays small.
+static CHUNKS: [Chunk; CHUNK_COUNT] = [EMPTY_CHUNK; CHUNK_COUNT];
+
+/// Index of the interned codes by their string, only used while reading data and parameters.
+static INDEX: RwLock<BTreeMap<&'static str, u32>> = RwLock::new(BTreeMap::new());
+
+/// Chunk and position in the chunk of the code with the 0-based index `idx`.
+fn locate(idx: usize) -> (usize, usize) {
+    let chunk = (idx / FIRST_CHUNK + 1).ilog2() as usize;
+    (chunk, idx - FIRST_CHUNK * ((1 << chunk) - 1))
+}
+
+/// Interned code, e.g. a country, feature or timezone code.
+///
+/// Only takes 4 bytes and doesn't allocate per city, as each distinct code is stored once for
+/// the whole process. Dereferences to `str` and compares with strings.
+///
+/// # Examples
+/// ```rust
+/// let code = geocoder::Code::from("PPLA");
+/// assert_eq!(code, "PPLA");
+/// assert_eq!(code, geocoder::Code::from(String::from("PPLA")));
+/// assert!(geocoder::Code::default().is_empty());
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Code(u32);
+
+impl Code {
+    /// The code as a string slice.
+    pub fn as_str(&self) -> &'static str {
+        if self.0 == 0 {
+            return "";
+        }
+        let (chunk, slot) = locate(self.0 as usize - 1);
+        CHUNKS[chunk]
+            .get()
+            .and_then(|chunk| chunk[slot].get())
+            .expect("codes are only created once interned")
+    }
+}
+
+impl Code {
+    /// The code if it was interned before, doesn't intern new strings.
+    pub(crate) fn get(code: &str) -> Option<Code> {
+        match code {
+            "" => Some(Code(0)),
+            code => {
+                let index = INDEX.read().unwrap_or_else(PoisonError::into_inner);
+                index.get(code).map(|idx| Code(*idx))
+            }
+        }
+    }
+}
+
+impl From<&str> for Code {
+    fn from(code: &str) -> Self {
+        if let Some(code) = Code::get(code) {
+            return code;
+        }
+        // A panic can't leave the index inconsistent, a code is only added once it is stored
+        let mut index = INDEX.write().unwrap_or_else(PoisonError::into_inner);
+        if let Some(idx) = index.get(code) {
+            return Code(*idx);
+        }
+        let (chunk, slot) = locate(index.len());
+        let chunk = CHUNKS[chunk].get_or_init(|| {
+            let size = FIRST_CHUNK << chunk;
+            (0..size).map(|_| OnceCell::new()).collect()
+        });
+        let code: &'static str = Box::leak(code.into());
+        chunk[slot].set(code).expect("slots are only set once");
+        let idx = index.len() as u32 + 1;
+        index.insert(code, idx);
+        Code(idx)
+    }
+}
+
+impl From<String> for Code {
+    fn from(code: String) -> Self {
+        Code::from(code.as_str())
+    }
+}
+
+impl std::ops::Deref for Code {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq<str> for Code {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Code {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for Code {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl Debug for Code {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl Display for Code {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for Code {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Code {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CodeVisitor;
+
+        impl Visitor<'_> for CodeVisitor {
+            type Value = Code;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("a string")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, code: &str) -> Result<Code, E> {
+                Ok(Code::from(code))
+            }
+        }
+
+        deserializer.deserialize_str(CodeVisitor)
+    }
+}
+
+/// Returns `true` if the allow-list is empty or contains the code.
+pub(crate) fn is_allowed(allowed: &[String], code: &str) -> bool {
+    allowed.is_empty() || allowed.iter().any(|allowed| code == allowed)
+}
+
+/// Name, ASCII name and alternate names of a city, stored in a single allocation.
+///
+/// If the ASCII name equals the name, it is only stored once. An empty ASCII name is treated
+/// the same way and returns the name.
+///
+/// # Examples
+/// ```rust
+/// let names = geocoder::Names::new("Köln", "Koeln", "Cologne,Colonia");
+/// assert_eq!(names.name(), "Köln");
+/// assert_eq!(names.asciiname(), "Koeln");
+/// assert_eq!(names.alternatenames(), "Cologne,Colonia");
+///
+/// let names = geocoder::Names::from("Erkelenz");
+/// assert_eq!(names.asciiname(), "Erkelenz");
+/// ```
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct Names {
+    text: Box<str>,
+    name_end: u32,
+    asciiname_end: u32,
+}
+
+impl Names {
+    pub fn new(name: &str, asciiname: &str, alternatenames: &str) -> Names {
+        let asciiname = if asciiname == name { "" } else { asciiname };
+        let mut text = String::with_capacity(name.len() + asciiname.len() + alternatenames.len());
+        text.push_str(name);
+        text.push_str(asciiname);
+        text.push_str(alternatenames);
+        Names {
+            text: text.into_boxed_str(),
+            name_end: name.len() as u32,
+            asciiname_end: (name.len() + asciiname.len()) as u32,
+        }
+    }
+
+    /// Name of geographical point (utf8)
+    pub fn name(&self) -> &str {
+        &self.text[..self.name_end as usize]
+    }
+
+    /// Name of geographical point in plain ascii characters
+    pub fn asciiname(&self) -> &str {
+        match &self.text[self.name_end as usize..self.asciiname_end as usize] {
+            "" => self.name(),
+            asciiname => asciiname,
+        }
+    }
+
+    /// Alternate names, comma separated
+    pub fn alternatenames(&self) -> &str {
+        &self.text[self.asciiname_end as usize..]
+    }
+
+    /// Drop the alternate names to save memory.
+    pub fn drop_alternatenames(&mut self) {
+        let asciiname = &self.text[self.name_end as usize..self.asciiname_end as usize];
+        *self = Names::new(self.name(), asciiname, "");
+    }
+}
+
+impl From<&str> for Names {
+    fn from(name: &str) -> Self {
+        Names::new(name, name, "")
+    }
+}
+
+impl Debug for Names {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Names")
+            .field("name", &self.name())
+            .field("asciiname", &self.asciiname())
+            .field("alternatenames", &self.alternatenames())
+            .finish()
+    }
+}
+
+thread_local! {
+    /// Keys of the last properties created on this thread. All cities of a data file have the
+    /// same keys, so they are shared instead of being stored per city.
+    static LAST_KEYS: RefCell<Option<Arc<[Box<str>]>>> = const { RefCell::new(None) };
+}
+
+/// The keys of the previous properties created on this thread if they are equal to `keys`.
+fn shared_keys(keys: Vec<Box<str>>) -> Option<Arc<[Box<str>]>> {
+    if keys.is_empty() {
+        return None;
+    }
+    LAST_KEYS.with(|last| {
+        let mut last = last.borrow_mut();
+        match &*last {
+            Some(last) if **last == *keys => Some(last.clone()),
+            _ => last.insert(keys.into()).clone().into(),
+        }
+    })
+}
+
+/// Additional properties of a city as key-value pairs, e.g. the passthrough columns of a
+/// [`Schema`](crate::Schema). Empty properties don't allocate, the keys are shared with the
+/// properties created before if they are the same.
+///
+/// # Examples
+/// ```rust
+/// let properties = geocoder::Properties::from_iter([("phone", "+49 221 123")]);
+/// assert_eq!(properties.get("phone"), Some("+49 221 123"));
+/// assert_eq!(properties.get("email"), None);
+/// ```
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct Properties {
+    keys: Option<Arc<[Box<str>]>>,
+    values: Box<[Box<str>]>,
+}
+
+impl Properties {
+    /// Value of the property `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.iter().find(|(k, _)| *k == key).map(|(_, value)| value)
+    }
+
+    /// All properties in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        let keys = self.keys.as_deref().unwrap_or_default();
+        keys.iter()
+            .zip(self.values.iter())
+            .map(|(key, value)| (key.as_ref(), value.as_ref()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl<K: Into<Box<str>>, V: Into<Box<str>>> FromIterator<(K, V)> for Properties {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let (keys, values): (Vec<Box<str>>, Vec<Box<str>>) = iter
+            .into_iter()
+            .map(|(key, value)| (key.into(), value.into()))
+            .unzip();
+        Properties {
+            keys: shared_keys(keys),
+            values: values.into(),
+        }
+    }
+}
+
+impl Debug for Properties {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Serialized as a sequence of key-value pairs.
+impl Serialize for Properties {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for Properties {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pairs = Vec::<(Box<str>, Box<str>)>::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interns_codes_once() {
+        let code = Code::from("Europe/Berlin");
+        assert_eq!(code, Code::from(String::from("Europe/Berlin")));
+        assert_ne!(code, Code::from("Europe/Paris"));
+        assert_eq!(std::mem::size_of::<Code>(), 4);
+        assert_eq!(
+            format!("{} {:?}", code, code),
+            "Europe/Berlin \"Europe/Berlin\""
+        );
+
+        let bytes = bincode::serialize(&code).unwrap();
+        assert_eq!(bincode::deserialize::<Code>(&bytes).unwrap(), code);
+        assert_eq!(
+            bincode::deserialize::<String>(&bytes).unwrap(),
+            "Europe/Berlin"
+        );
+    }
+
+    #[test]
+    fn locates_codes_in_chunks() {
+        assert_eq!(locate(0), (0, 0));
+        assert_eq!(locate(63), (0, 63));
+        assert_eq!(locate(64), (1, 0));
+        assert_eq!(locate(191), (1, 127));
+        assert_eq!(locate(192), (2, 0));
+        assert!(locate(u32::MAX as usize).0 < CHUNK_COUNT);
+    }
+
+    #[test]
+    fn interns_codes_across_chunks() {
+        let codes: Vec<Code> = (0..300).map(|i| Code::from(format!("chunk-{i}"))).collect();
+        for (i, code) in codes.iter().enumerate() {
+            assert_eq!(code.as_str(), format!("chunk-{i}"));
+        }
+    }
+
+    #[test]
+    fn shares_keys_of_properties() {
+        let a = Properties::from_iter([("phone", "1"), ("email", "a@b.c")]);
+        let b = Properties::from_iter([("phone", "2"), ("email", "")]);
+        assert!(Arc::ptr_eq(
+            a.keys.as_ref().unwrap(),
+            b.keys.as_ref().unwrap()
+        ));
+        assert_eq!(b.get("phone"), Some("2"));
+        assert_eq!(format!("{:?}", a), r#"{"phone": "1", "email": "a@b.c"}"#);
+
+        let bytes = bincode::serialize(&a).unwrap();
+        assert_eq!(bincode::deserialize::<Properties>(&bytes).unwrap(), a);
+        let pairs: Vec<(String, String)> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(pairs[1], (String::from("email"), String::from("a@b.c")));
+        assert_eq!(Properties::default().iter().count(), 0);
+    }
+
+    #[test]
+    fn drops_alternatenames() {
+        let mut names = Names::new("Köln", "Koln", "Cologne,Colonia");
+        names.drop_alternatenames();
+        assert_eq!(names.name(), "Köln");
+        assert_eq!(names.asciiname(), "Koln");
+        assert_eq!(names.alternatenames(), "");
+
+        let mut names = Names::new("Bonn", "", "Bonna");
+        names.drop_alternatenames();
+        assert_eq!(names.asciiname(), "Bonn");
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{errors, parse_csv_file, City, ReverseGeocoder};
+
+/// Column names of the countryInfo.txt export, used in error messages.
+const COUNTRY_INFO_COLUMNS: [&str; 19] = [
+    "ISO",
+    "ISO3",
+    "ISO-Numeric",
+    "fips",
+    "Country",
+    "Capital",
+    "Area(in sq km)",
+    "Population",
+    "Continent",
+    "tld",
+    "CurrencyCode",
+    "CurrencyName",
+    "Phone",
+    "Postal Code Format",
+    "Postal Code Regex",
+    "Languages",
+    "geonameid",
+    "neighbours",
+    "EquivalentFipsCode",
+];
+
+/// Country structure, as defined in the http://www.geonames.org export `countryInfo.txt`.
+#[rustfmt::skip]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Country {
+    pub iso: String,                  // ISO-3166 2-letter country code
+    pub iso3: String,                 // ISO-3166 3-letter country code
+    pub iso_numeric: String,          // ISO-3166 numeric country code, zero-padded
+    pub fips: String,                 // FIPS 10-4 country code
+    pub name: String,                 // English country name
+    pub capital: String,              // name of the capital
+    pub area: Option<f64>,            // area in square kilometres
+    pub population: Option<u64>,      // population
+    pub continent: String,            // continent code, e.g. "EU"
+    pub tld: String,                  // top-level domain, e.g. ".de"
+    pub currency_code: String,        // ISO-4217 currency code
+    pub currency_name: String,        // currency name
+    pub phone: String,                // international dialing code
+    pub postal_code_format: String,   // postal code format, e.g. "#####"
+    pub postal_code_regex: String,    // regular expression matching postal codes
+    pub languages: String,            // spoken languages, comma separated, ordered by number of speakers
+    pub id: Option<u32>,              // geonameid of the country
+    pub neighbours: String,           // ISO-3166 2-letter codes of neighbouring countries, comma separated
+    pub equivalent_fips_code: String, // equivalent FIPS code
+}
+
+impl Country {
+    /// Spoken languages, ordered by number of speakers.
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        split_list(&self.languages)
+    }
+
+    /// ISO-3166 2-letter codes of neighbouring countries.
+    pub fn neighbours(&self) -> impl Iterator<Item = &str> {
+        split_list(&self.neighbours)
+    }
+}
+
+impl Display for Country {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',').filter(|s| !s.is_empty())
+}
+
+/// Lookup table of countries by their ISO-3166 2-letter code.
+pub(crate) type Countries = HashMap<String, Country>;
+
+impl ReverseGeocoder {
+    /// Load country metadata from a GeoNames `countryInfo.txt`.
+    ///
+    /// Replaces previously loaded countries.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// let mut gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// gc.load_country_info("countryInfo.txt")?;
+    /// # Ok::<(), geocoder::errors::Error>(())
+    /// ```
+    pub fn load_country_info(&mut self, filename: &str) -> errors::Result<()> {
+        let (countries, _) =
+            parse_csv_file::<Country, _>(filename, &COUNTRY_INFO_COLUMNS, false, |_| true)?;
+        tracing::info!("Loaded {} countries", countries.len());
+        self.countries = countries
+            .into_iter()
+            .map(|country| (country.iso.clone(), country))
+            .collect();
+        Ok(())
+    }
+
+    /// Resolve the country of the given city.
+    ///
+    /// Returns `None` if no country metadata is loaded or the country code is unknown.
+    pub fn country(&self, city: &City) -> Option<&Country> {
+        self.countries.get(city.country_code.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::write_test_file;
+    use tracing_test::traced_test;
+
+    //noinspection SpellCheckingInspection
+    #[test]
+    #[traced_test]
+    fn joins_country_info() {
+        let countries = write_test_file(
+            "countryInfo.txt",
+            "# GeoNames.org Country Information\n\
+             #ISO\tISO3\tISO-Numeric\tfips\tCountry\tCapital\tArea(in sq km)\tPopulation\tContinent\ttld\tCurrencyCode\tCurrencyName\tPhone\tPostal Code Format\tPostal Code Regex\tLanguages\tgeonameid\tneighbours\tEquivalentFipsCode\n\
+             DE\tDEU\t276\tGM\tGermany\tBerlin\t357021\t82927922\tEU\t.de\tEUR\tEuro\t49\t#####\t^(\\d{5})$\tde\t2921044\tCH,PL,NL,DK,BE,CZ,LU,FR,AT\t\n",
+        );
+        let mut gc = ReverseGeocoder::from_file("../cities.txt");
+        gc.load_country_info(&countries).unwrap();
+
+        let (_, city) = gc.search(51.08, 6.31, 1)[0];
+        let country = gc.country(city).unwrap();
+        assert_eq!(country.name, "Germany");
+        assert_eq!(country.iso3, "DEU");
+        assert_eq!(country.population, Some(82927922));
+        assert_eq!(country.languages().collect::<Vec<_>>(), vec!["de"]);
+        assert_eq!(country.neighbours().count(), 9);
+
+        let (_, city) = gc.search(35.69, 139.69, 1)[0];
+        assert_eq!(gc.country(city), None);
+    }
+}
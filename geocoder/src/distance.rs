@@ -0,0 +1,145 @@
+//! Great-circle distances between WGS84 coordinates.
+
+use serde::Deserialize;
+
+/// Mean earth radius in metres, as used by the haversine formula
+pub const EARTH_RADIUS_IN_METRES: f64 = 6_371_000.0;
+
+/// Semi-major axis of the WGS84 ellipsoid in metres
+const WGS84_A: f64 = 6_378_137.0;
+/// Flattening of the WGS84 ellipsoid
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+/// Semi-minor axis of the WGS84 ellipsoid in metres
+const WGS84_B: f64 = WGS84_A * (1.0 - WGS84_F);
+
+/// Maximum number of iterations of Vincenty's formula before giving up
+const VINCENTY_MAX_ITERATIONS: usize = 200;
+
+/// Formula used to calculate the distance between two points.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DistanceMethod {
+    /// Great-circle distance on a sphere with a radius of [`EARTH_RADIUS_IN_METRES`].
+    #[default]
+    Haversine,
+    /// Geodesic distance on the WGS84 ellipsoid. Accurate to less than a millimetre, but slower.
+    /// Falls back to haversine for nearly antipodal points, where the formula doesn't converge.
+    Vincenty,
+}
+
+impl DistanceMethod {
+    /// Distance in metres between two points given in decimal degrees.
+    pub fn distance(&self, lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
+        match self {
+            DistanceMethod::Haversine => haversine(lat1, lng1, lat2, lng2),
+            DistanceMethod::Vincenty => vincenty(lat1, lng1, lat2, lng2)
+                .unwrap_or_else(|| haversine(lat1, lng1, lat2, lng2)),
+        }
+    }
+}
+
+/// Great-circle distance in metres between two points given in decimal degrees.
+///
+/// # Example
+/// ```rust
+/// let d = geocoder::distance::haversine(51.0, 6.0, 51.0, 6.001);
+/// assert_eq!(d.round(), 70.0);
+/// ```
+pub fn haversine(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lng = (lng2 - lng1).to_radians();
+
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lng / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_IN_METRES * a.sqrt().min(1.0).asin()
+}
+
+/// Geodesic distance in metres on the WGS84 ellipsoid between two points given in decimal
+/// degrees, using [Vincenty's inverse formula](https://en.wikipedia.org/wiki/Vincenty%27s_formulae).
+///
+/// Returns `None` if the formula doesn't converge, which may happen for nearly antipodal points.
+pub fn vincenty(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> Option<f64> {
+    let l = (lng2 - lng1).to_radians();
+    let u1 = ((1.0 - WGS84_F) * lat1.to_radians().tan()).atan();
+    let u2 = ((1.0 - WGS84_F) * lat2.to_radians().tan()).atan();
+    let (sin_u1, cos_u1) = u1.sin_cos();
+    let (sin_u2, cos_u2) = u2.sin_cos();
+
+    let mut lambda = l;
+    for _ in 0..VINCENTY_MAX_ITERATIONS {
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        let sin_sigma = ((cos_u2 * sin_lambda).powi(2)
+            + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda).powi(2))
+        .sqrt();
+        if sin_sigma == 0.0 {
+            // coincident points
+            return Some(0.0);
+        }
+        let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+        let sigma = sin_sigma.atan2(cos_sigma);
+        let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+        let cos_sq_alpha = 1.0 - sin_alpha * sin_alpha;
+        let cos_2sigma_m = if cos_sq_alpha == 0.0 {
+            // equatorial line
+            0.0
+        } else {
+            cos_sigma - 2.0 * sin_u1 * sin_u2 / cos_sq_alpha
+        };
+        let c = WGS84_F / 16.0 * cos_sq_alpha * (4.0 + WGS84_F * (4.0 - 3.0 * cos_sq_alpha));
+
+        let previous = lambda;
+        lambda = l
+            + (1.0 - c)
+                * WGS84_F
+                * sin_alpha
+                * (sigma
+                    + c * sin_sigma
+                        * (cos_2sigma_m + c * cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))));
+
+        if (lambda - previous).abs() < 1e-12 {
+            let u_sq = cos_sq_alpha * (WGS84_A.powi(2) - WGS84_B.powi(2)) / WGS84_B.powi(2);
+            let a =
+                1.0 + u_sq / 16384.0 * (4096.0 + u_sq * (-768.0 + u_sq * (320.0 - 175.0 * u_sq)));
+            let b = u_sq / 1024.0 * (256.0 + u_sq * (-128.0 + u_sq * (74.0 - 47.0 * u_sq)));
+            let delta_sigma = b
+                * sin_sigma
+                * (cos_2sigma_m
+                    + b / 4.0
+                        * (cos_sigma * (-1.0 + 2.0 * cos_2sigma_m.powi(2))
+                            - b / 6.0
+                                * cos_2sigma_m
+                                * (-3.0 + 4.0 * sin_sigma.powi(2))
+                                * (-3.0 + 4.0 * cos_2sigma_m.powi(2))));
+            return Some(WGS84_B * a * (sigma - delta_sigma));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calculates_haversine_distance() {
+        assert_eq!(haversine(51.0, 6.0, 51.0, 6.0), 0.0);
+        // one degree of latitude
+        let d = haversine(0.0, 0.0, 1.0, 0.0);
+        assert!((d - 111_194.9).abs() < 0.1, "{}", d);
+        // antipodes
+        let d = haversine(0.0, 0.0, 0.0, 180.0);
+        assert!((d - std::f64::consts::PI * EARTH_RADIUS_IN_METRES).abs() < 1e-6);
+    }
+
+    #[test]
+    fn calculates_vincenty_distance() {
+        assert_eq!(vincenty(51.0, 6.0, 51.0, 6.0), Some(0.0));
+        // Flinders Peak to Buninyong, 54972.271 m on the Australian National Spheroid
+        let d = vincenty(-37.951_033_4, 144.424_867_9, -37.652_821_1, 143.926_495_3).unwrap();
+        assert!((d - 54_972.289).abs() < 0.001, "{}", d);
+        // nearly antipodal points don't converge
+        assert_eq!(vincenty(0.0, 0.0, 0.5, 179.7), None);
+        let d = DistanceMethod::Vincenty.distance(0.0, 0.0, 0.5, 179.7);
+        assert_eq!(d, haversine(0.0, 0.0, 0.5, 179.7));
+    }
+}
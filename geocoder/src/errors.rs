@@ -1,3 +1,5 @@
+use std::fmt::{Display, Formatter};
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("IO error: {0}")]
@@ -5,6 +7,82 @@ pub enum Error {
 
     #[error("CSV error: {0}")]
     CSVError(#[from] csv::Error),
+
+    #[error("Zip error: {0}")]
+    ZipError(#[from] zip::result::ZipError),
+
+    #[error("{file}: {row}")]
+    InvalidRow { file: String, row: RowError },
+
+    #[error("{file}: invalid snapshot: {reason}")]
+    InvalidSnapshot { file: String, reason: String },
+
+    #[error("{file}: invalid boundaries: {reason}")]
+    InvalidBoundaries { file: String, reason: String },
+
+    #[error("invalid schema: {0}")]
+    InvalidSchema(String),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
+
+/// Describes a single row of a data file that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowError {
+    /// Line number (1-based) of the row in the data file.
+    pub line: u64,
+    /// Name of the offending column, if the error can be attributed to one.
+    pub column: Option<String>,
+    /// Human-readable description of what went wrong.
+    pub reason: String,
+}
+
+impl Display for RowError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.column {
+            Some(column) => write!(
+                f,
+                "line {}, column '{}': {}",
+                self.line, column, self.reason
+            ),
+            None => write!(f, "line {}: {}", self.line, self.reason),
+        }
+    }
+}
+
+impl RowError {
+    /// Extract row-level diagnostics from a CSV error.
+    ///
+    /// Returns `None` if the error is not caused by the contents of a single row (e.g. I/O errors),
+    /// so the caller can treat it as fatal. `columns` maps field indices to column names.
+    pub(crate) fn from_csv(err: &csv::Error, columns: &[&str]) -> Option<RowError> {
+        let line = err.position().map(|pos| pos.line()).unwrap_or(0);
+        let column_name = |idx: u64| {
+            columns
+                .get(idx as usize)
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| format!("#{}", idx + 1))
+        };
+
+        match err.kind() {
+            csv::ErrorKind::Deserialize { err, .. } => Some(RowError {
+                line,
+                column: err.field().map(column_name),
+                reason: err.kind().to_string(),
+            }),
+            csv::ErrorKind::UnequalLengths {
+                expected_len, len, ..
+            } => Some(RowError {
+                line,
+                column: None,
+                reason: format!("expected {} columns, found {}", expected_len, len),
+            }),
+            csv::ErrorKind::Utf8 { err, .. } => Some(RowError {
+                line,
+                column: Some(column_name(err.field() as u64)),
+                reason: String::from("invalid UTF-8"),
+            }),
+            _ => None,
+        }
+    }
+}
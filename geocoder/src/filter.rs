@@ -0,0 +1,288 @@
+use kiddo::float::distance::squared_euclidean;
+
+use crate::compact::is_allowed;
+use crate::{
+    degrees_lat_lng_to_unit_sphere, kilometres_to_unit_sphere_squared_euclidean, City, CityRef,
+    Float, Locatable, ReverseGeocoder,
+};
+
+/// Restricts search results to cities matching all of the given criteria.
+///
+/// Empty lists and `None` values don't restrict the results.
+///
+/// # Examples
+/// ```rust
+/// let filter = geocoder::Filter {
+///     feature_codes: vec![String::from("PPLA"), String::from("PPLC")],
+///     min_population: Some(10000),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    /// Feature class, e.g. "P" for populated places
+    pub feature_class: Option<String>,
+    /// Allowed feature codes, e.g. "PPLA" or "PPLC"
+    pub feature_codes: Vec<String>,
+    /// Minimum population, cities without population are excluded
+    pub min_population: Option<u32>,
+    /// Allowed ISO-3166 2-letter country codes
+    pub country_codes: Vec<String>,
+}
+
+impl Filter {
+    /// Returns `true` if the filter doesn't restrict the results at all.
+    pub fn is_empty(&self) -> bool {
+        self == &Filter::default()
+    }
+
+    /// Returns `true` if the city matches all criteria.
+    pub fn matches(&self, city: &City) -> bool {
+        self.matches_fields(
+            &city.feature_class,
+            &city.feature_code,
+            &city.country_code,
+            city.population,
+        )
+    }
+
+    /// Like [`Filter::matches`], for a city of a [`MappedGeocoder`](crate::MappedGeocoder).
+    pub fn matches_ref(&self, city: &CityRef) -> bool {
+        self.matches_fields(
+            city.feature_class(),
+            city.feature_code(),
+            city.country_code(),
+            city.population(),
+        )
+    }
+
+    fn matches_fields(
+        &self,
+        feature_class: &str,
+        feature_code: &str,
+        country_code: &str,
+        population: Option<u32>,
+    ) -> bool {
+        if let Some(allowed) = &self.feature_class {
+            if feature_class != allowed {
+                return false;
+            }
+        }
+        if let Some(min_population) = self.min_population {
+            if population.unwrap_or(0) < min_population {
+                return false;
+            }
+        }
+        is_allowed(&self.feature_codes, feature_code)
+            && is_allowed(&self.country_codes, country_code)
+    }
+}
+
+impl ReverseGeocoder {
+    /// Finds the `results` cities nearest to the given coordinates that match the `filter`,
+    /// within `max_distance_km` kilometres if given.
+    ///
+    /// The tree is searched with a growing number of neighbours until enough matching cities
+    /// are found, all cities have been checked or the neighbours are farther away than
+    /// `max_distance_km`. Returns an empty Vec if there is no matching city within that
+    /// distance.
+    ///
+    /// # Example
+    /// ```rust
+    /// # let gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// let filter = geocoder::Filter {
+    ///     country_codes: vec![String::from("JP")],
+    ///     ..Default::default()
+    /// };
+    /// let results = gc.search_filtered(50.93, 6.95, 1, None, &filter);
+    /// assert_eq!(results[0].1.name(), "Osaka");
+    /// ```
+    pub fn search_filtered(
+        &self,
+        lat: Float,
+        lng: Float,
+        results: usize,
+        max_distance_km: Option<Float>,
+        filter: &Filter,
+    ) -> Vec<(f64, &City)> {
+        if filter.is_empty() && max_distance_km.is_none() {
+            return self.search(lat, lng, results);
+        }
+        tracing::debug!(
+            "Searching for {} cities closest to {};{} matching {:?}",
+            results,
+            lat,
+            lng,
+            filter
+        );
+        self.search_matching(lat, lng, results, max_distance_km, |city| {
+            filter.matches(city)
+        })
+    }
+}
+
+impl<T: Locatable> ReverseGeocoder<T> {
+    /// Finds the `results` records nearest to the given coordinates for which `predicate`
+    /// returns `true`, like [`ReverseGeocoder::search_filtered`] for records other than cities.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use geocoder::{Float, Locatable, ReverseGeocoder};
+    /// struct Store {
+    ///     open_on_sunday: bool,
+    ///     lat: Float,
+    ///     lng: Float,
+    /// }
+    ///
+    /// impl Locatable for Store {
+    ///     fn coordinates(&self) -> (Float, Float) {
+    ///         (self.lat, self.lng)
+    ///     }
+    /// }
+    ///
+    /// let gc = ReverseGeocoder::new(vec![
+    ///     Store { open_on_sunday: false, lat: 50.94, lng: 6.96 },
+    ///     Store { open_on_sunday: true, lat: 52.52, lng: 13.40 },
+    /// ]);
+    /// let results = gc.search_matching(51.08, 6.31, 1, None, |store| store.open_on_sunday);
+    /// assert_eq!(results[0].1.lat, 52.52);
+    /// ```
+    pub fn search_matching<P>(
+        &self,
+        lat: Float,
+        lng: Float,
+        results: usize,
+        max_distance_km: Option<Float>,
+        predicate: P,
+    ) -> Vec<(f64, &T)>
+    where
+        P: Fn(&T) -> bool,
+    {
+        let query = degrees_lat_lng_to_unit_sphere(lat, lng);
+        let max_distance = max_distance_km
+            .map(kilometres_to_unit_sphere_squared_euclidean)
+            .unwrap_or(Float::INFINITY);
+        let size = self.tree.size();
+        let mut qty = results.saturating_mul(4).max(16).min(size);
+        loop {
+            let mut neighbours = self.tree.nearest_n(&query, qty, &squared_euclidean);
+            let exhausted = qty == size
+                || neighbours.len() < qty
+                || matches!(neighbours.last(), Some(n) if n.distance > max_distance);
+            neighbours.retain(|n| n.distance <= max_distance);
+            let mut found = self.to_results(lat, lng, neighbours);
+            found.retain(|(_, item)| predicate(item));
+            if found.len() >= results || exhausted {
+                found.truncate(results);
+                return found;
+            }
+            qty = qty.saturating_mul(4).min(size);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Code;
+    use tracing_test::traced_test;
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let filter = Filter::default();
+        assert!(filter.is_empty());
+        assert!(filter.matches(&City::default()));
+    }
+
+    //noinspection SpellCheckingInspection
+    #[test]
+    fn matches_all_criteria() {
+        let city = City {
+            feature_class: Code::from("P"),
+            feature_code: Code::from("PPLA2"),
+            country_code: Code::from("DE"),
+            population: Some(44650),
+            ..Default::default()
+        };
+        let filter = |f: fn(&mut Filter)| {
+            let mut filter = Filter {
+                feature_class: Some(String::from("P")),
+                feature_codes: vec![String::from("PPLA2"), String::from("PPLC")],
+                min_population: Some(10000),
+                country_codes: vec![String::from("DE")],
+            };
+            f(&mut filter);
+            filter.matches(&city)
+        };
+
+        assert!(filter(|_| {}));
+        assert!(!filter(|f| f.feature_class = Some(String::from("A"))));
+        assert!(!filter(|f| f.feature_codes = vec![String::from("PPL")]));
+        assert!(!filter(|f| f.min_population = Some(50000)));
+        assert!(!filter(|f| f.country_codes = vec![String::from("NL")]));
+    }
+
+    #[test]
+    #[traced_test]
+    fn keeps_searching_until_enough_matches_are_found() {
+        let city = |id: u32, lat: Float, feature_code: &str| City {
+            id,
+            latitude: lat,
+            feature_code: feature_code.into(),
+            ..Default::default()
+        };
+        let mut cities: Vec<City> = (0..200)
+            .map(|i| city(i, i as Float * 0.01, "PPL"))
+            .collect();
+        cities.push(city(1000, 10.0, "PPLA"));
+        cities.push(city(1001, -20.0, "PPLA"));
+        let gc = ReverseGeocoder::new(cities);
+        let filter = Filter {
+            feature_codes: vec![String::from("PPLA")],
+            ..Default::default()
+        };
+
+        let results = gc.search_filtered(0.0, 0.0, 1, None, &filter);
+        let ids: Vec<u32> = results.iter().map(|(_, c)| c.id).collect();
+        assert_eq!(ids, vec![1000]);
+
+        let results = gc.search_filtered(0.0, 0.0, 5, None, &filter);
+        let ids: Vec<u32> = results.iter().map(|(_, c)| c.id).collect();
+        assert_eq!(ids, vec![1000, 1001]);
+
+        let filter = Filter {
+            min_population: Some(1),
+            ..Default::default()
+        };
+        assert!(gc.search_filtered(0.0, 0.0, 2, None, &filter).is_empty());
+
+        let filter = Filter {
+            feature_codes: vec![String::from("PPLA")],
+            ..Default::default()
+        };
+        assert_eq!(
+            gc.search_filtered(0.0, 0.0, usize::MAX, None, &filter)
+                .len(),
+            2
+        );
+        assert_eq!(gc.search(0.0, 0.0, usize::MAX).len(), 202);
+        let empty = ReverseGeocoder::new(vec![]);
+        assert!(empty.search_filtered(0.0, 0.0, 5, None, &filter).is_empty());
+    }
+
+    #[test]
+    #[traced_test]
+    fn drops_results_beyond_max_distance() {
+        let gc = ReverseGeocoder::from_file("../cities.txt");
+        let filter = Filter::default();
+
+        // Pacific Ocean
+        assert!(gc
+            .search_filtered(-30.0, -140.0, 1, Some(2000.0), &filter)
+            .is_empty());
+
+        let results = gc.search_filtered(51.0, 6.3, 5, Some(100.0), &filter);
+        let ids: Vec<u32> = results.iter().map(|(_, c)| c.id).collect();
+        assert_eq!(ids, vec![2929622]);
+    }
+}
@@ -1,85 +1,353 @@
-mod errors;
+mod admin;
+mod batch;
+mod boundary;
+mod compact;
+mod country;
+pub mod distance;
+pub mod errors;
+mod filter;
+mod localized;
+mod locatable;
+mod mapped;
+mod names;
+mod options;
+mod postal;
+mod schema;
+mod snapshot;
+mod timezone;
+mod update;
 
+use std::collections::HashMap;
+use std::f64::consts;
 use std::fmt::{Display, Formatter};
 use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
 
 use csv::ReaderBuilder;
+use flate2::read::MultiGzDecoder;
 use kiddo::float::neighbour::Neighbour;
 use kiddo::float::{distance::squared_euclidean, kdtree::KdTree};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use zip::result::ZipError;
+use zip::ZipArchive;
 
-const EARTH_RADIUS_IN_KM: f32 = 6371.0;
+use crate::admin::AdminDivisions;
+use crate::country::Countries;
+use crate::errors::{Error, RowError};
+use crate::localized::LocalizedNames;
+use crate::names::NameIndex;
+
+pub use crate::admin::AdminDivision;
+pub use crate::batch::BatchResults;
+pub use crate::boundary::{Boundaries, Boundary};
+pub use crate::compact::{Code, Names, Properties};
+pub use crate::country::Country;
+pub use crate::distance::DistanceMethod;
+pub use crate::filter::Filter;
+pub use crate::locatable::Locatable;
+pub use crate::mapped::{is_mapped, CityRef, MappedGeocoder};
+pub use crate::options::LoadOptions;
+pub use crate::postal::PostalCode;
+pub use crate::schema::Schema;
+pub use crate::snapshot::is_snapshot;
+pub use crate::timezone::LocalTime;
+pub use crate::update::{Changes, UpdateReport};
+
+/// Floating point type of coordinates, the kd-tree and its distance calculations.
+///
+/// `f32` by default, `f64` if the `f64` feature is enabled.
+#[cfg(not(feature = "f64"))]
+pub type Float = f32;
+#[cfg(feature = "f64")]
+pub type Float = f64;
+
+const EARTH_RADIUS_IN_KM: Float = 6371.0;
+const PI: Float = consts::PI as Float;
+
+/// Added to the search radius of bounding box queries to compensate for rounding errors.
+const BBOX_TOLERANCE: Float = 1e-5;
+
+/// First bytes of a zip archive.
+const ZIP_MAGIC: [u8; 4] = [0x50, 0x4b, 0x03, 0x04];
+/// First bytes of a gzip file.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Column names of the http://www.geonames.org export, used in error messages.
+const GEONAMES_COLUMNS: [&str; 19] = [
+    "geonameid",
+    "name",
+    "asciiname",
+    "alternatenames",
+    "latitude",
+    "longitude",
+    "feature class",
+    "feature code",
+    "country code",
+    "cc2",
+    "admin1 code",
+    "admin2 code",
+    "admin3 code",
+    "admin4 code",
+    "population",
+    "elevation",
+    "dem",
+    "timezone",
+    "modification date",
+];
 
 /// City structure, as defined in the http://www.geonames.org export.
 ///
+/// Codes with few distinct values are interned and the names share a single allocation, see
+/// [`Code`] and [`Names`], so a city takes about 180 bytes plus the length of its names, admin3
+/// and admin4 codes, dem and modification date.
+///
 /// # Examples
 /// ```rust
 /// let city = geocoder::City {
 ///     id: 1,
-///     name: String::from("Nikolassee"),
-///     asciiname: String::from("Nikolassee"),
-///     alternatenames: String::from("Berlin-Nikolassee,Nicolassee,Nikolassee,Nikolasze,Николасзе"),
+///     names: geocoder::Names::new(
+///         "Nikolassee",
+///         "Nikolassee",
+///         "Berlin-Nikolassee,Nicolassee,Nikolassee,Nikolasze,Николасзе",
+///     ),
 ///     latitude: 52.4344,
 ///     longitude: 13.20095,
-///     feature_class: String::from("P"),
-///     feature_code: String::from("PPLX"),
-///     country_code: String::from("DE"),
-///     cc2: String::from("16"),
-///     admin1_code: String::from("00"),
-///     admin2_code: String::from("11000"),
-///     admin3_code: String::from("11000000"),
-///     admin4_code: String::from("11642"),
+///     feature_class: "P".into(),
+///     feature_code: "PPLX".into(),
+///     country_code: "DE".into(),
+///     cc2: "16".into(),
+///     admin1_code: "00".into(),
+///     admin2_code: "11000".into(),
+///     admin3_code: "11000000".into(),
+///     admin4_code: "11642".into(),
 ///     population: None,
 ///     elevation: Some(42),
-///     dem: String::from(""),
-///     timezone: String::from("Europe/Berlin"),
-///     modification_date: String::from("2022-08-04"),
+///     dem: "".into(),
+///     timezone: "Europe/Berlin".into(),
+///     modification_date: "2022-08-04".into(),
+///     properties: Default::default(),
 /// };
+/// assert_eq!(city.name(), "Nikolassee");
 /// ```
 #[rustfmt::skip]
-#[derive(Debug, Clone, Default, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(from = "GeoNamesRow", into = "GeoNamesRow")]
 pub struct City {
-    pub id: u32,                   // integer id of record in geonames database
-    pub name: String,              // name of geographical point (utf8) varchar(200)
-    pub asciiname: String,         // name of geographical point in plain ascii characters, varchar(200)
-    pub alternatenames: String,    // alternatenames, comma separated, ascii names automatically transliterated, convenience attribute from alternatename table, varchar(10000)
-    pub latitude: f32,             // latitude in decimal degrees (wgs84)
-    pub longitude: f32,            // longitude in decimal degrees (wgs84)
-    pub feature_class: String,     // see http://www.geonames.org/export/codes.html, char(1)
-    pub feature_code: String,      // see http://www.geonames.org/export/codes.html, varchar(10)
-    pub country_code: String,      // ISO-3166 2-letter country code, 2 characters
-    pub cc2: String,               // alternate country codes, comma separated, ISO-3166 2-letter country code, 200 characters
-    pub admin1_code: String,       // fipscode (subject to change to iso code), see exceptions below, see file admin1Codes.txt for display names of this code; varchar(20)
-    pub admin2_code: String,       // code for the second administrative division, a county in the US, see file admin2Codes.txt; varchar(80)
-    pub admin3_code: String,       // code for third level administrative division, varchar(20)
-    pub admin4_code: String,       // code for fourth level administrative division, varchar(20)
-    pub population: Option<u32>,   // bigint (8 byte int)
-    pub elevation: Option<i16>,    // in metres, integer
-    pub dem: String,               // digital elevation model, srtm3 or gtopo30, average elevation of 3''x3'' (ca 90mx90m) or 30''x30'' (ca 900mx900m) area in metres, integer. srtm processed by cgiar/ciat.
-    pub timezone: String,          // the IANA timezone id (see file timeZone.txt) varchar(40)
-    pub modification_date: String, // date of last modification in yyyy-MM-dd format
+    pub id: u32,                     // integer id of record in geonames database
+    pub names: Names,                // name, asciiname and alternatenames, see below
+    pub latitude: Float,             // latitude in decimal degrees (wgs84)
+    pub longitude: Float,            // longitude in decimal degrees (wgs84)
+    pub feature_class: Code,         // see http://www.geonames.org/export/codes.html, char(1)
+    pub feature_code: Code,          // see http://www.geonames.org/export/codes.html, varchar(10)
+    pub country_code: Code,          // ISO-3166 2-letter country code, 2 characters
+    pub cc2: Code,                   // alternate country codes, comma separated, ISO-3166 2-letter country code, 200 characters
+    pub admin1_code: Code,           // fipscode (subject to change to iso code), see exceptions below, see file admin1Codes.txt for display names of this code; varchar(20)
+    pub admin2_code: Code,           // code for the second administrative division, a county in the US, see file admin2Codes.txt; varchar(80)
+    pub admin3_code: Box<str>,       // code for third level administrative division, varchar(20)
+    pub admin4_code: Box<str>,       // code for fourth level administrative division, varchar(20)
+    pub population: Option<u32>,     // bigint (8 byte int)
+    pub elevation: Option<i16>,      // in metres, integer
+    pub dem: Box<str>,               // digital elevation model, srtm3 or gtopo30, average elevation of 3''x3'' (ca 90mx90m) or 30''x30'' (ca 900mx900m) area in metres, integer. srtm processed by cgiar/ciat.
+    pub timezone: Code,              // the IANA timezone id (see file timeZone.txt) varchar(40)
+    pub modification_date: Box<str>, // date of last modification in yyyy-MM-dd format
+    pub properties: Properties,      // not part of the export, passthrough columns of a custom Schema
+}
+
+/// Row of the http://www.geonames.org export, only used to (de)serialize a [`City`].
+#[rustfmt::skip]
+#[derive(Deserialize, Serialize)]
+struct GeoNamesRow {
+    id: u32,
+    name: String,                  // name of geographical point (utf8) varchar(200)
+    asciiname: String,             // name of geographical point in plain ascii characters, varchar(200)
+    alternatenames: String,        // alternatenames, comma separated, ascii names automatically transliterated, convenience attribute from alternatename table, varchar(10000)
+    latitude: Float,
+    longitude: Float,
+    feature_class: Code,
+    feature_code: Code,
+    country_code: Code,
+    cc2: Code,
+    admin1_code: Code,
+    admin2_code: Code,
+    admin3_code: Box<str>,
+    admin4_code: Box<str>,
+    population: Option<u32>,
+    elevation: Option<i16>,
+    dem: Box<str>,
+    timezone: Code,
+    modification_date: Box<str>,
+    #[serde(default)]
+    properties: Properties,
+}
+
+impl From<GeoNamesRow> for City {
+    fn from(row: GeoNamesRow) -> Self {
+        City {
+            id: row.id,
+            names: Names::new(&row.name, &row.asciiname, &row.alternatenames),
+            latitude: row.latitude,
+            longitude: row.longitude,
+            feature_class: row.feature_class,
+            feature_code: row.feature_code,
+            country_code: row.country_code,
+            cc2: row.cc2,
+            admin1_code: row.admin1_code,
+            admin2_code: row.admin2_code,
+            admin3_code: row.admin3_code,
+            admin4_code: row.admin4_code,
+            population: row.population,
+            elevation: row.elevation,
+            dem: row.dem,
+            timezone: row.timezone,
+            modification_date: row.modification_date,
+            properties: row.properties,
+        }
+    }
+}
+
+impl From<City> for GeoNamesRow {
+    fn from(city: City) -> Self {
+        GeoNamesRow {
+            id: city.id,
+            name: city.name().to_string(),
+            asciiname: city.asciiname().to_string(),
+            alternatenames: city.alternatenames().to_string(),
+            latitude: city.latitude,
+            longitude: city.longitude,
+            feature_class: city.feature_class,
+            feature_code: city.feature_code,
+            country_code: city.country_code,
+            cc2: city.cc2,
+            admin1_code: city.admin1_code,
+            admin2_code: city.admin2_code,
+            admin3_code: city.admin3_code,
+            admin4_code: city.admin4_code,
+            population: city.population,
+            elevation: city.elevation,
+            dem: city.dem,
+            timezone: city.timezone,
+            modification_date: city.modification_date,
+            properties: city.properties,
+        }
+    }
 }
 
 impl City {
+    /// Name of geographical point (utf8)
+    pub fn name(&self) -> &str {
+        self.names.name()
+    }
+
+    /// Name of geographical point in plain ascii characters
+    pub fn asciiname(&self) -> &str {
+        self.names.asciiname()
+    }
+
+    /// Alternate names, comma separated, ascii names automatically transliterated
+    pub fn alternatenames(&self) -> &str {
+        self.names.alternatenames()
+    }
+
     /// Get coordinates as ECEF (x;y;z)-coordinates.
-    pub fn as_xyz(&self) -> [f32; 3] {
+    pub fn as_xyz(&self) -> [Float; 3] {
         degrees_lat_lng_to_unit_sphere(self.latitude, self.longitude)
     }
+
+    /// Get (latitude; longitude) as f64, with the same digits as in the source file.
+    ///
+    /// Widening an f32 directly adds float artefacts, e.g. `78.2233` becomes
+    /// `78.22329711914062`. Instead, the shortest decimal that maps to the same f32 is used,
+    /// which is the original value unless it had more significant digits than an f32 can hold.
+    /// Enable the `f64` feature to keep all digits.
+    ///
+    /// # Example
+    /// ```rust
+    /// let city = geocoder::City {
+    ///     latitude: 78.2233,
+    ///     longitude: 15.64689,
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(city.lat_lng(), (78.2233, 15.64689));
+    /// ```
+    pub fn lat_lng(&self) -> (f64, f64) {
+        (to_f64(self.latitude), to_f64(self.longitude))
+    }
 }
 
 impl Display for City {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}, {}", self.name, self.country_code)
+        write!(f, "{}, {}", self.name(), self.country_code)
     }
 }
 
+/// Summary of a lenient load, see [`ReverseGeocoder::from_file_lenient`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadReport {
+    /// Number of rows read from the data file, including skipped ones.
+    pub rows_read: u64,
+    /// Rows that could not be parsed and were skipped.
+    pub skipped: Vec<RowError>,
+    /// Number of rows that were dropped by the [`LoadOptions`].
+    pub rows_filtered: u64,
+}
+
+impl LoadReport {
+    /// Number of rows that were skipped.
+    pub fn rows_skipped(&self) -> u64 {
+        self.skipped.len() as u64
+    }
+
+    /// Record an invalid row if loading is `lenient`, otherwise fail with it.
+    pub(crate) fn skip(
+        &mut self,
+        row: RowError,
+        filename: &str,
+        lenient: bool,
+    ) -> errors::Result<()> {
+        if !lenient {
+            return Err(Error::InvalidRow {
+                file: filename.to_string(),
+                row,
+            });
+        }
+        self.skipped.push(row);
+        Ok(())
+    }
+}
+
+impl Display for LoadReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} rows read, {} rows skipped, {} rows filtered",
+            self.rows_read,
+            self.rows_skipped(),
+            self.rows_filtered
+        )
+    }
+}
+
+/// Reverse geocoder over records of type `T`, GeoNames cities by default.
+///
+/// See [`Locatable`] to index other records.
 #[derive(Debug)]
-pub struct ReverseGeocoder {
-    cities: Vec<City>,
-    tree: KdTree<f32, usize, 3, 32, u16>,
+pub struct ReverseGeocoder<T: Locatable = City> {
+    cities: Vec<T>,
+    tree: KdTree<Float, usize, 3, 32, u16>,
+    ids: HashMap<u32, usize>,
+    names: NameIndex,
+    admin1: AdminDivisions,
+    admin2: AdminDivisions,
+    countries: Countries,
+    localized: LocalizedNames,
+    country_boundaries: Boundaries,
+    admin1_boundaries: Boundaries,
+    timezone_boundaries: Boundaries,
+    distance_method: DistanceMethod,
 }
 
-impl Display for ReverseGeocoder {
+impl<T: Locatable> Display for ReverseGeocoder<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
@@ -90,17 +358,27 @@ impl Display for ReverseGeocoder {
     }
 }
 
-impl Default for ReverseGeocoder {
+impl<T: Locatable> Default for ReverseGeocoder<T> {
     fn default() -> Self {
         Self {
             tree: KdTree::with_capacity(0),
             cities: vec![],
+            ids: HashMap::new(),
+            names: NameIndex::default(),
+            admin1: AdminDivisions::new(),
+            admin2: AdminDivisions::new(),
+            countries: Countries::new(),
+            localized: LocalizedNames::new(),
+            country_boundaries: Boundaries::default(),
+            admin1_boundaries: Boundaries::default(),
+            timezone_boundaries: Boundaries::default(),
+            distance_method: DistanceMethod::default(),
         }
     }
 }
 
-impl ReverseGeocoder {
-    /// Initialize ReverseGeocoder with a list of cities.
+impl<T: Locatable> ReverseGeocoder<T> {
+    /// Initialize ReverseGeocoder with a list of cities or other [`Locatable`] records.
     ///
     /// This will fill the tree with the [ECEF coordinates](https://en.wikipedia.org/wiki/Earth-centered,_Earth-fixed_coordinate_system)
     /// and the city's index in the given vector, so the tree's payload doesn't contain the
@@ -111,34 +389,29 @@ impl ReverseGeocoder {
     /// let cities: Vec<geocoder::City> = vec![ /* ... */];
     /// let gc = geocoder::ReverseGeocoder::new(cities);
     /// ```
-    pub fn new(cities: Vec<City>) -> ReverseGeocoder {
-        let mut tree: KdTree<f32, usize, 3, 32, u16> = KdTree::with_capacity(cities.len());
+    pub fn new(cities: Vec<T>) -> ReverseGeocoder<T> {
+        let mut tree: KdTree<Float, usize, 3, 32, u16> = KdTree::with_capacity(cities.len());
         cities.iter().enumerate().for_each(|(idx, city)| {
-            tree.add(&city.as_xyz(), idx);
+            let (lat, lng) = city.coordinates();
+            tree.add(&degrees_lat_lng_to_unit_sphere(lat, lng), idx);
         });
         tracing::info!("Populated tree with {} cities", cities.len());
+        let ids = id_index(&cities);
+        let names = NameIndex::new(&cities);
 
-        Self { cities, tree }
-    }
-
-    /// Initialize ReverseGeocoder from a CSV file.
-    ///
-    /// Loads a CSV file into memory and initializes the GeoCoder with the contents.
-    /// Currently only supported format is http://www.geonames.org citiesX.txt
-    ///
-    /// # Example
-    /// ```rust
-    /// let gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
-    /// ```
-    pub fn from_file(csv_path: &str) -> ReverseGeocoder {
-        let cities: Vec<City> = parse_csv_file(csv_path).expect("panic!");
-        Self::new(cities)
+        Self {
+            cities,
+            tree,
+            ids,
+            names,
+            ..Default::default()
+        }
     }
 
     /// Finds the `results` cities nearest to the given coordinates (WGS84, decimal format).
     ///
-    /// Returns a Vec of tuples consisting of the distance in kilometres to the given coordinates and
-    /// the found `City`..
+    /// Returns a Vec of tuples consisting of the distance in metres to the given coordinates and
+    /// the found `City`, nearest first.
     ///
     /// # Arguments
     /// * `lat` - latitude
@@ -150,7 +423,7 @@ impl ReverseGeocoder {
     /// # let gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
     /// let results = gc.search(47.11, 8.15, 10);
     /// ```
-    pub fn search(&self, lat: f32, lng: f32, results: usize) -> Vec<(u32, &City)> {
+    pub fn search(&self, lat: Float, lng: Float, results: usize) -> Vec<(f64, &T)> {
         tracing::debug!(
             "Searching for {} cities closest to {};{}",
             results,
@@ -159,41 +432,450 @@ impl ReverseGeocoder {
         );
 
         let query = degrees_lat_lng_to_unit_sphere(lat, lng);
+        // kiddo reserves space for all requested neighbours
+        let results = results.min(self.tree.size());
         let results = self.tree.nearest_n(&query, results, &squared_euclidean);
         tracing::debug!("Found: {:?}", results);
-        results
+        self.to_results(lat, lng, results)
+    }
+
+    /// Finds all cities within `km` kilometres (great-circle distance) of the given coordinates.
+    ///
+    /// Returns a Vec of tuples consisting of the distance in metres to the given coordinates and
+    /// the found `City`, nearest first.
+    ///
+    /// # Arguments
+    /// * `lat` - latitude
+    /// * `lng` - longitude
+    /// * `km` - search radius in kilometres
+    ///
+    /// # Example
+    /// ```rust
+    /// # let gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// let results = gc.within_radius(35.0, 137.0, 500.0);
+    /// assert_eq!(results.len(), 2);
+    /// ```
+    pub fn within_radius(&self, lat: Float, lng: Float, km: Float) -> Vec<(f64, &T)> {
+        tracing::debug!("Searching for cities within {} km of {};{}", km, lat, lng);
+
+        let query = degrees_lat_lng_to_unit_sphere(lat, lng);
+        let radius = kilometres_to_unit_sphere_squared_euclidean(km);
+        let results = self.tree.within(&query, radius, &squared_euclidean);
+        tracing::debug!("Found: {:?}", results);
+        self.to_results(lat, lng, results)
+    }
+
+    /// Finds all cities within the given bounding box (WGS84, decimal format).
+    ///
+    /// Boxes crossing the antimeridian are supported by passing a `min_lng` greater than
+    /// `max_lng`, e.g. `170.0` and `-170.0`. Results are returned in no particular order.
+    ///
+    /// # Example
+    /// ```rust
+    /// # let gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// let results = gc.within_bbox(30.0, 130.0, 40.0, 140.0);
+    /// assert_eq!(results.len(), 2);
+    /// ```
+    pub fn within_bbox(
+        &self,
+        min_lat: Float,
+        min_lng: Float,
+        max_lat: Float,
+        max_lng: Float,
+    ) -> Vec<&T> {
+        tracing::debug!(
+            "Searching for cities within {};{} - {};{}",
+            min_lat,
+            min_lng,
+            max_lat,
+            max_lng
+        );
+
+        let bbox = BoundingBox {
+            min_lat,
+            min_lng,
+            max_lat,
+            max_lng,
+        };
+        let contains = |city: &T| {
+            let (lat, lng) = city.coordinates();
+            bbox.contains(lat, lng)
+        };
+        if bbox.is_empty() {
+            return vec![];
+        }
+        let Some((center, radius)) = bbox.circle() else {
+            return self.cities.iter().filter(|city| contains(city)).collect();
+        };
+        self.tree
+            .within_unsorted(&center, radius, &squared_euclidean)
             .iter()
-            .map(|Neighbour { distance, item }| {
-                (
-                    unit_sphere_squared_euclidean_to_kilometres(*distance) as u32,
-                    self.cities.get(*item).unwrap(),
-                )
+            .map(|Neighbour { item, .. }| &self.cities[*item])
+            .filter(|city| contains(city))
+            .collect()
+    }
+
+    /// All cities or other records, in the order they were loaded.
+    pub fn cities(&self) -> &[T] {
+        &self.cities
+    }
+
+    /// Finds the city with the given GeoNames id, or another record by its
+    /// [`Locatable::id`].
+    ///
+    /// # Example
+    /// ```rust
+    /// # let gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// let tokyo = gc.get(1850147).unwrap();
+    /// assert_eq!(tokyo.name(), "Tokyo");
+    /// ```
+    pub fn get(&self, id: u32) -> Option<&T> {
+        self.ids.get(&id).map(|idx| &self.cities[*idx])
+    }
+
+    /// Set the formula used to calculate the distances returned by searches.
+    ///
+    /// # Example
+    /// ```rust
+    /// # let mut gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// gc.set_distance_method(geocoder::DistanceMethod::Vincenty);
+    /// ```
+    pub fn set_distance_method(&mut self, method: DistanceMethod) {
+        self.distance_method = method;
+    }
+
+    /// Map tree results to cities and calculate their distances in metres to the query point.
+    #[allow(clippy::unnecessary_cast)] // Float is f64 with the "f64" feature
+    fn to_results(
+        &self,
+        lat: Float,
+        lng: Float,
+        neighbours: Vec<Neighbour<Float, usize>>,
+    ) -> Vec<(f64, &T)> {
+        neighbours
+            .iter()
+            .map(|Neighbour { item, .. }| {
+                let city = self.cities.get(*item).unwrap();
+                let (city_lat, city_lng) = city.coordinates();
+                let distance = self.distance_method.distance(
+                    lat as f64,
+                    lng as f64,
+                    city_lat as f64,
+                    city_lng as f64,
+                );
+                (distance, city)
             })
             .collect()
     }
 }
 
+impl ReverseGeocoder {
+    /// Initialize ReverseGeocoder from a CSV file.
+    ///
+    /// Loads a CSV file into memory and initializes the GeoCoder with the contents.
+    /// Currently only supported format is http://www.geonames.org citiesX.txt, which may also be
+    /// read directly from the `citiesX.zip` downloaded from GeoNames or a gzip file.
+    ///
+    /// # Panics
+    /// Panics if the file can't be read or contains invalid rows. Use
+    /// [`ReverseGeocoder::try_from_file`] to handle these errors.
+    ///
+    /// # Example
+    /// ```rust
+    /// let gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// ```
+    pub fn from_file(csv_path: &str) -> ReverseGeocoder {
+        Self::try_from_file(csv_path).unwrap_or_else(|e| panic!("Unable to load data: {}", e))
+    }
+
+    /// Initialize ReverseGeocoder from a CSV file, failing on the first invalid row.
+    ///
+    /// The returned error contains the file name, line number and column of the offending row.
+    ///
+    /// # Example
+    /// ```rust
+    /// let gc = geocoder::ReverseGeocoder::try_from_file("../cities.txt")?;
+    /// # Ok::<(), geocoder::errors::Error>(())
+    /// ```
+    pub fn try_from_file(csv_path: &str) -> errors::Result<ReverseGeocoder> {
+        let (cities, _) = parse_csv_file(csv_path, &GEONAMES_COLUMNS, false, |_| true)?;
+        Ok(Self::new(cities))
+    }
+
+    /// Initialize ReverseGeocoder from a CSV file, skipping invalid rows.
+    ///
+    /// Returns the geocoder together with a [`LoadReport`] listing the rows that were skipped.
+    /// I/O errors are still returned as errors.
+    ///
+    /// # Example
+    /// ```rust
+    /// let (gc, report) = geocoder::ReverseGeocoder::from_file_lenient("../cities.txt")?;
+    /// assert_eq!(report.rows_skipped(), 0);
+    /// # Ok::<(), geocoder::errors::Error>(())
+    /// ```
+    pub fn from_file_lenient(csv_path: &str) -> errors::Result<(ReverseGeocoder, LoadReport)> {
+        let options = LoadOptions {
+            skip_invalid_rows: true,
+            ..Default::default()
+        };
+        Self::from_file_with_options(csv_path, &options)
+    }
+
+    /// Initialize ReverseGeocoder from a CSV file, loading only the rows matching the `options`.
+    ///
+    /// Returns the geocoder together with a [`LoadReport`] counting the rows that were skipped
+    /// or filtered.
+    ///
+    /// # Example
+    /// ```rust
+    /// let options = geocoder::LoadOptions {
+    ///     country_codes: vec![String::from("DE")],
+    ///     ..Default::default()
+    /// };
+    /// let (gc, report) = geocoder::ReverseGeocoder::from_file_with_options("../cities.txt", &options)?;
+    /// assert_eq!(report.rows_filtered, 10);
+    /// # Ok::<(), geocoder::errors::Error>(())
+    /// ```
+    pub fn from_file_with_options(
+        csv_path: &str,
+        options: &LoadOptions,
+    ) -> errors::Result<(ReverseGeocoder, LoadReport)> {
+        let lenient = options.skip_invalid_rows;
+        let keep = |city: &mut City| options.apply(city);
+        let (cities, report) = match &options.schema {
+            Some(schema) => {
+                read_data_file(csv_path, |data| schema.read(data, csv_path, lenient, keep))?
+            }
+            None => parse_csv_file(csv_path, &GEONAMES_COLUMNS, lenient, keep)?,
+        };
+        for row in &report.skipped {
+            tracing::warn!("Skipping invalid row in {}: {}", csv_path, row);
+        }
+        Ok((Self::new(cities), report))
+    }
+}
+
+impl TryFrom<&str> for ReverseGeocoder {
+    type Error = Error;
+
+    fn try_from(csv_path: &str) -> errors::Result<Self> {
+        Self::try_from_file(csv_path)
+    }
+}
+
+/// Area of a bounding box query, see [`ReverseGeocoder::within_bbox`].
+pub(crate) struct BoundingBox {
+    pub(crate) min_lat: Float,
+    pub(crate) min_lng: Float,
+    pub(crate) max_lat: Float,
+    pub(crate) max_lng: Float,
+}
+
+impl BoundingBox {
+    fn crosses_antimeridian(&self) -> bool {
+        self.min_lng > self.max_lng
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.min_lat > self.max_lat
+    }
+
+    pub(crate) fn contains(&self, lat: Float, lng: Float) -> bool {
+        let lat_ok = lat >= self.min_lat && lat <= self.max_lat;
+        let lng_ok = if self.crosses_antimeridian() {
+            lng >= self.min_lng || lng <= self.max_lng
+        } else {
+            lng >= self.min_lng && lng <= self.max_lng
+        };
+        lat_ok && lng_ok
+    }
+
+    /// The smallest circle around the box's center that contains all corners, as ECEF center
+    /// and squared euclidean radius. Cities within the circle still need to be checked with
+    /// [`BoundingBox::contains`].
+    ///
+    /// Returns `None` if the box covers most of the globe, so a tree wouldn't prune much anyway.
+    /// This also keeps the corners the points farthest from the center.
+    pub(crate) fn circle(&self) -> Option<([Float; 3], Float)> {
+        let width = if self.crosses_antimeridian() {
+            self.max_lng + 360.0 - self.min_lng
+        } else {
+            self.max_lng - self.min_lng
+        };
+        if width > 180.0 {
+            return None;
+        }
+        let mut center_lng = self.min_lng + width / 2.0;
+        if center_lng > 180.0 {
+            center_lng -= 360.0;
+        }
+        let center =
+            degrees_lat_lng_to_unit_sphere((self.min_lat + self.max_lat) / 2.0, center_lng);
+        let radius = [
+            (self.min_lat, self.min_lng),
+            (self.min_lat, self.max_lng),
+            (self.max_lat, self.min_lng),
+            (self.max_lat, self.max_lng),
+        ]
+        .iter()
+        .map(|(lat, lng)| squared_euclidean(&center, &degrees_lat_lng_to_unit_sphere(*lat, *lng)))
+        .fold(0.0, Float::max);
+        Some((center, radius + BBOX_TOLERANCE))
+    }
+}
+
+/// Index of records by their [`Locatable::id`], for [`ReverseGeocoder::get`].
+fn id_index<T: Locatable>(items: &[T]) -> HashMap<u32, usize> {
+    items
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, item)| item.id().map(|id| (id, idx)))
+        .collect()
+}
+
 /// Parse CSV file into Vec of `R`.
-fn parse_csv_file<R: for<'de> serde::Deserialize<'de>>(filename: &str) -> errors::Result<Vec<R>> {
+///
+/// `columns` names the columns for error messages. If `lenient` is set, rows that can't be parsed
+/// are skipped and recorded in the returned [`LoadReport`], otherwise the first invalid row fails
+/// the whole file. Lines starting with `#` are treated as comments. Records for which `keep`
+/// returns `false` are dropped and counted in [`LoadReport::rows_filtered`], `keep` may also
+/// modify the record.
+///
+/// The file is read with [`read_data_file`], so it may also be a zip or gzip archive.
+fn parse_csv_file<R, K>(
+    filename: &str,
+    columns: &[&str],
+    lenient: bool,
+    keep: K,
+) -> errors::Result<(Vec<R>, LoadReport)>
+where
+    R: for<'de> serde::Deserialize<'de>,
+    K: FnMut(&mut R) -> bool,
+{
+    read_data_file(filename, |data| {
+        parse_csv(data, filename, columns, lenient, keep)
+    })
+}
+
+/// Open a data file and pass its contents to `read`.
+///
+/// Zip and gzip archives are detected by their contents and decompressed while reading. Of a zip
+/// archive, the `.txt` file named like the archive is read, e.g. `cities500.txt` of
+/// `cities500.zip`, or the only `.txt` file besides a readme.
+pub(crate) fn read_data_file<T, F>(filename: &str, read: F) -> errors::Result<T>
+where
+    F: FnOnce(&mut dyn Read) -> errors::Result<T>,
+{
     tracing::debug!("Loading from file {}", filename);
-    let file = File::open(filename)?;
+    let mut file = File::open(filename)?;
+    let mut magic = [0u8; 4];
+    let magic = match file.read(&mut magic)? {
+        4 => magic,
+        _ => [0u8; 4],
+    };
+    file.rewind()?;
+
+    if magic == ZIP_MAGIC {
+        let mut archive = ZipArchive::new(file)?;
+        let name = zip_entry(&archive, filename)?;
+        let mut entry = archive.by_name(&name)?;
+        tracing::debug!("Reading {} from zip archive", entry.name());
+        read(&mut entry)
+    } else if magic[..2] == GZIP_MAGIC {
+        read(&mut MultiGzDecoder::new(file))
+    } else {
+        read(&mut file)
+    }
+}
+
+/// Name of the data file in a zip archive, see [`read_data_file`].
+fn zip_entry<F: Read + Seek>(archive: &ZipArchive<F>, filename: &str) -> errors::Result<String> {
+    let stem = Path::new(filename)
+        .file_stem()
+        .map(|stem| format!("{}.txt", stem.to_string_lossy()));
+    let txt_files: Vec<&str> = archive
+        .file_names()
+        .filter(|name| name.ends_with(".txt") && !name.eq_ignore_ascii_case("readme.txt"))
+        .collect();
+
+    match txt_files.iter().find(|name| Some(name.to_string()) == stem) {
+        Some(name) => Ok(name.to_string()),
+        None if txt_files.len() == 1 => Ok(txt_files[0].to_string()),
+        None => Err(ZipError::FileNotFound.into()),
+    }
+}
+
+fn parse_csv<R, K, D>(
+    data: D,
+    filename: &str,
+    columns: &[&str],
+    lenient: bool,
+    mut keep: K,
+) -> errors::Result<(Vec<R>, LoadReport)>
+where
+    R: for<'de> serde::Deserialize<'de>,
+    K: FnMut(&mut R) -> bool,
+    D: Read,
+{
     let mut reader = ReaderBuilder::new()
         .has_headers(false)
         .delimiter(b'\t')
-        .from_reader(file);
-    let records = reader.deserialize().collect::<Result<Vec<R>, _>>()?;
-    Ok(records)
+        .comment(Some(b'#'))
+        .from_reader(data);
+
+    let mut records = vec![];
+    let mut report = LoadReport::default();
+    for result in reader.deserialize() {
+        report.rows_read += 1;
+        match result {
+            Ok(mut record) => {
+                if keep(&mut record) {
+                    records.push(record);
+                } else {
+                    report.rows_filtered += 1;
+                }
+            }
+            Err(e) => match RowError::from_csv(&e, columns) {
+                Some(row) => report.skip(row, filename, lenient)?,
+                None => return Err(e.into()),
+            },
+        }
+    }
+    Ok((records, report))
+}
+
+/// Convert a coordinate to f64 without adding digits, see [`City::lat_lng`]
+#[cfg(not(feature = "f64"))]
+fn to_f64(value: Float) -> f64 {
+    value.to_string().parse().unwrap_or(value.into())
+}
+
+#[cfg(feature = "f64")]
+fn to_f64(value: Float) -> f64 {
+    value
 }
 
 /// Convert geodetic coordinates to ECEF coordinates
-fn degrees_lat_lng_to_unit_sphere(lat: f32, lng: f32) -> [f32; 3] {
+fn degrees_lat_lng_to_unit_sphere(lat: Float, lng: Float) -> [Float; 3] {
     let lat = lat.to_radians();
     let lng = lng.to_radians();
     [lat.cos() * lng.cos(), lat.cos() * lng.sin(), lat.sin()]
 }
 
+/// Convert a great-circle distance in kilometres to the squared chord length on the unit sphere
+pub fn kilometres_to_unit_sphere_squared_euclidean(km: Float) -> Float {
+    let angle = (km / EARTH_RADIUS_IN_KM).clamp(0.0, PI);
+    let chord = 2.0 * (angle / 2.0).sin();
+    chord * chord
+}
+
+/// Approximate distance between two ECEF coordinates in kilometres
+fn kilometres_between(a: &[Float; 3], b: &[Float; 3]) -> Float {
+    unit_sphere_squared_euclidean_to_kilometres(squared_euclidean(a, b))
+}
+
 /// Convert distance between two ECEF coordinates to kilometres
-pub fn unit_sphere_squared_euclidean_to_kilometres(sq_euc_dist: f32) -> f32 {
+pub fn unit_sphere_squared_euclidean_to_kilometres(sq_euc_dist: Float) -> Float {
     sq_euc_dist.sqrt() * EARTH_RADIUS_IN_KM
 }
 
@@ -205,7 +887,7 @@ mod tests {
     #[test]
     #[traced_test]
     fn doesnt_find_anything_if_list_is_empty() {
-        let gc = ReverseGeocoder::new(vec![]);
+        let gc: ReverseGeocoder = ReverseGeocoder::new(vec![]);
         let result = gc.search(50.93, 6.95, 1);
         assert!(result.is_empty());
     }
@@ -214,9 +896,161 @@ mod tests {
     #[traced_test]
     fn finds_test_city() {
         let gc = ReverseGeocoder::from_file("../cities.txt");
-        let (d, city) = gc.search(50.88, 6.92, 1).first().unwrap().clone();
+        let (d, city) = *gc.search(50.88, 6.92, 1).first().unwrap();
         assert_eq!(city.id, 2929622);
-        assert_eq!(d, 47);
+        assert_eq!(d.round(), 47791.0);
         assert_eq!(format!("{}", city), "Erkelenz, DE")
     }
+
+    #[test]
+    #[traced_test]
+    fn finds_cities_within_radius() {
+        let gc = ReverseGeocoder::from_file("../cities.txt");
+
+        let ids: Vec<u32> = gc
+            .within_radius(35.0, 137.0, 500.0)
+            .iter()
+            .map(|(_, city)| city.id)
+            .collect();
+        assert_eq!(ids, vec![1853909, 1850147]);
+
+        assert!(gc.within_radius(35.0, 137.0, 10.0).is_empty());
+        assert_eq!(gc.within_radius(0.0, 0.0, 30000.0).len(), 11);
+    }
+
+    #[test]
+    #[traced_test]
+    fn finds_cities_within_bbox() {
+        let gc = ReverseGeocoder::from_file("../cities.txt");
+        let ids = |min_lat, min_lng, max_lat, max_lng| {
+            let mut ids: Vec<u32> = gc
+                .within_bbox(min_lat, min_lng, max_lat, max_lng)
+                .iter()
+                .map(|city| city.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(ids(30.0, 130.0, 40.0, 140.0), vec![1850147, 1853909]);
+        assert_eq!(ids(30.0, 135.6, 40.0, 140.0), vec![1850147]);
+        assert!(ids(40.0, 130.0, 30.0, 140.0).is_empty());
+        // crossing the antimeridian
+        assert_eq!(ids(-10.0, 120.0, 10.0, -170.0), vec![1645457]);
+        assert!(ids(-10.0, 130.0, 10.0, -170.0).is_empty());
+        // wider than half the globe
+        assert_eq!(ids(-90.0, -180.0, 90.0, 180.0).len(), 11);
+        assert_eq!(ids(-30.0, -120.0, 60.0, 100.0).len(), 6);
+        assert_eq!(ids(20.0, 100.0, 40.0, -100.0).len(), 4);
+    }
+
+    #[test]
+    fn converts_kilometres_to_chord_length() {
+        assert_eq!(kilometres_to_unit_sphere_squared_euclidean(0.0), 0.0);
+        let half_circumference = PI * EARTH_RADIUS_IN_KM;
+        assert!(
+            (kilometres_to_unit_sphere_squared_euclidean(half_circumference) - 4.0).abs() < 1e-4
+        );
+        assert!((kilometres_to_unit_sphere_squared_euclidean(1e6) - 4.0).abs() < 1e-4);
+    }
+
+    pub(crate) fn test_file_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("geocoder-{}-{}", std::process::id(), name));
+        path.to_string_lossy().to_string()
+    }
+
+    pub(crate) fn write_test_file(name: &str, contents: &str) -> String {
+        let path = test_file_path(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn broken_file(name: &str) -> String {
+        let valid = std::fs::read_to_string("../cities.txt").unwrap();
+        let mut lines: Vec<&str> = valid.lines().collect();
+        let broken = lines[2].replacen("23.7104", "north", 1);
+        lines[2] = &broken;
+        write_test_file(name, &lines.join("\n"))
+    }
+
+    #[test]
+    #[traced_test]
+    fn reports_file_line_and_column_of_invalid_row() {
+        let path = broken_file("strict.txt");
+        let err = ReverseGeocoder::try_from_file(&path).unwrap_err();
+        match err {
+            Error::InvalidRow { file, row } => {
+                assert_eq!(file, path);
+                assert_eq!(row.line, 3);
+                assert_eq!(row.column.as_deref(), Some("latitude"));
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    #[traced_test]
+    fn lenient_load_skips_invalid_rows() {
+        let path = broken_file("lenient.txt");
+        let (gc, report) = ReverseGeocoder::from_file_lenient(&path).unwrap();
+        assert_eq!(report.rows_read, 11);
+        assert_eq!(report.rows_skipped(), 1);
+        assert_eq!(report.skipped[0].line, 3);
+        assert_eq!(format!("{}", gc), "ReverseGeocoder<cities=10, tree=10>");
+    }
+
+    #[test]
+    #[traced_test]
+    fn reads_zip_and_gzip_archives() {
+        use std::io::Write;
+        let data = std::fs::read("../cities.txt").unwrap();
+        // Concatenated members, like the output of parallel compressors such as pigz
+        let gz = test_file_path("cities.txt.gz");
+        let mut file = File::create(&gz).unwrap();
+        let middle = data.len() / 2
+            + data[data.len() / 2..]
+                .iter()
+                .position(|b| *b == b'\n')
+                .unwrap();
+        for part in [&data[..=middle], &data[middle + 1..]] {
+            let mut encoder =
+                flate2::write::GzEncoder::new(&mut file, flate2::Compression::default());
+            encoder.write_all(part).unwrap();
+            encoder.finish().unwrap();
+        }
+        assert_eq!(
+            ReverseGeocoder::try_from_file(&gz).unwrap().cities.len(),
+            11
+        );
+
+        let zip = |name: &str, entries: &[&str]| {
+            let mut writer = zip::ZipWriter::new(File::create(test_file_path(name)).unwrap());
+            for entry in entries {
+                writer
+                    .start_file(*entry, zip::write::FileOptions::default())
+                    .unwrap();
+                let contents: &[u8] = if entry.starts_with("cities") {
+                    &data
+                } else {
+                    b""
+                };
+                writer.write_all(contents).unwrap();
+            }
+            writer.finish().unwrap();
+            test_file_path(name)
+        };
+        let gc = ReverseGeocoder::try_from_file(&zip("cities.zip", &["readme.txt", "cities.txt"]));
+        assert_eq!(gc.unwrap().cities.len(), 11);
+        let gc = ReverseGeocoder::try_from_file(&zip("other.zip", &["cities.txt"]));
+        assert_eq!(gc.unwrap().cities.len(), 11);
+        let gc = ReverseGeocoder::try_from_file(&zip("ambiguous.zip", &["a.txt", "b.txt"]));
+        assert!(matches!(gc, Err(Error::ZipError(ZipError::FileNotFound))));
+    }
+
+    #[test]
+    #[traced_test]
+    fn missing_file_is_an_error() {
+        let result = ReverseGeocoder::try_from("../does-not-exist.txt");
+        assert!(matches!(result, Err(Error::IOError(_))));
+    }
 }
@@ -0,0 +1,194 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Deserializer};
+
+use crate::{errors, parse_csv_file, City, Code, ReverseGeocoder};
+
+/// Column names of the alternateNamesV2.txt export, used in error messages.
+const ALTERNATE_NAMES_COLUMNS: [&str; 10] = [
+    "alternateNameId",
+    "geonameid",
+    "isolanguage",
+    "alternate name",
+    "isPreferredName",
+    "isShortName",
+    "isColloquial",
+    "isHistoric",
+    "from",
+    "to",
+];
+
+/// Row of the http://www.geonames.org export `alternateNamesV2.txt`.
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct AlternateName {
+    id: u32,
+    geonameid: u32,
+    isolanguage: String,
+    name: String,
+    #[serde(deserialize_with = "flag")]
+    is_preferred_name: bool,
+    #[serde(deserialize_with = "flag")]
+    is_short_name: bool,
+    #[serde(deserialize_with = "flag")]
+    is_colloquial: bool,
+    #[serde(deserialize_with = "flag")]
+    is_historic: bool,
+    #[serde(default)]
+    from: String,
+    #[serde(default)]
+    to: String,
+}
+
+impl AlternateName {
+    /// Lower is better: preferred names first, short names last.
+    fn rank(&self) -> u8 {
+        match (self.is_preferred_name, self.is_short_name) {
+            (true, false) => 0,
+            (true, true) => 1,
+            (false, false) => 2,
+            (false, true) => 3,
+        }
+    }
+}
+
+/// Flags are `1` if set and empty otherwise.
+fn flag<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    Ok(String::deserialize(deserializer)? == "1")
+}
+
+/// Returns `true` for ISO-639 language codes, `false` for the pseudo codes GeoNames uses for
+/// postal codes, links, airport codes etc.
+fn is_language(code: &str) -> bool {
+    let primary = code.split('-').next().unwrap_or_default();
+    (2..=3).contains(&primary.len()) && primary.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+/// Lookup table of the best name of a city in a language, by geonameid and lower case language.
+pub(crate) type LocalizedNames = HashMap<(u32, Code), Box<str>>;
+
+impl ReverseGeocoder {
+    /// Load localized names from a GeoNames `alternateNamesV2.txt`.
+    ///
+    /// Only names of loaded cities are kept, and only in the given `languages` unless it's
+    /// empty. Per city and language, a preferred name is chosen over other names, and short,
+    /// colloquial and historic names are avoided. Replaces previously loaded names.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// let mut gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// gc.load_alternate_names("alternateNamesV2.zip", &["de", "ja", "ar"])?;
+    /// # Ok::<(), geocoder::errors::Error>(())
+    /// ```
+    pub fn load_alternate_names<S: AsRef<str>>(
+        &mut self,
+        filename: &str,
+        languages: &[S],
+    ) -> errors::Result<()> {
+        let ids: HashSet<u32> = self.cities.iter().map(|city| city.id).collect();
+        let languages: Vec<String> = languages
+            .iter()
+            .map(|lang| lang.as_ref().to_lowercase())
+            .collect();
+        // Rows are folded into the best name per city and language while reading, instead of
+        // collecting all of them first
+        let mut best: HashMap<(u32, Code), (u8, Box<str>)> = HashMap::new();
+        let (_, report) = parse_csv_file::<AlternateName, _>(
+            filename,
+            &ALTERNATE_NAMES_COLUMNS,
+            false,
+            |name| {
+                name.isolanguage.make_ascii_lowercase();
+                let wanted = ids.contains(&name.geonameid)
+                    && !name.is_colloquial
+                    && !name.is_historic
+                    && is_language(&name.isolanguage)
+                    && (languages.is_empty() || languages.contains(&name.isolanguage));
+                if wanted {
+                    let key = (name.geonameid, Code::from(name.isolanguage.as_str()));
+                    let rank = name.rank();
+                    match best.get(&key) {
+                        Some((best_rank, _)) if *best_rank <= rank => {}
+                        _ => {
+                            best.insert(key, (rank, std::mem::take(&mut name.name).into()));
+                        }
+                    }
+                }
+                false
+            },
+        )?;
+        tracing::info!(
+            "Loaded {} localized names of {} alternate names",
+            best.len(),
+            report.rows_read
+        );
+        self.localized = best
+            .into_iter()
+            .map(|(key, (_, name))| (key, name))
+            .collect();
+        Ok(())
+    }
+
+    /// Name of the city in the first of the `languages` it has a name in.
+    ///
+    /// Languages are tried with and without their region, e.g. `de-CH` and then `de`. Returns
+    /// `None` if no alternate names are loaded or none matches.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # let mut gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// gc.load_alternate_names("alternateNamesV2.zip", &["de"])?;
+    /// let (_, city) = gc.search(35.69, 139.69, 1)[0];
+    /// assert_eq!(gc.localized_name(city, &["de-CH", "en"]), Some("Tokio"));
+    /// # Ok::<(), geocoder::errors::Error>(())
+    /// ```
+    pub fn localized_name<S: AsRef<str>>(&self, city: &City, languages: &[S]) -> Option<&str> {
+        if self.localized.is_empty() {
+            return None;
+        }
+        languages.iter().find_map(|lang| {
+            let lang = lang.as_ref().to_lowercase();
+            let primary = lang.split('-').next().unwrap_or_default();
+            let name = [lang.as_str(), primary]
+                .into_iter()
+                .filter_map(Code::get)
+                .find_map(|lang| self.localized.get(&(city.id, lang)));
+            name.map(|name| name.as_ref())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::write_test_file;
+    use tracing_test::traced_test;
+
+    //noinspection SpellCheckingInspection
+    #[test]
+    #[traced_test]
+    fn picks_preferred_names_per_language() {
+        let names = write_test_file(
+            "alternateNamesV2.txt",
+            "1\t1850147\tja\t東京都\t\t\t\t\t\t\n\
+             2\t1850147\tja\t東京\t1\t\t\t\t\t\n\
+             3\t1850147\tde\tTokio\t\t\t\t\t\t\n\
+             4\t1850147\tfr\tTokyo\t\t\t\t\t\t\n\
+             5\t1850147\tar\tطوكيو\t\t1\t\t\t\t\n\
+             6\t1850147\tar\tطوكيو القديمة\t\t\t\t1\t\t\n\
+             7\t1850147\tlink\thttps://en.wikipedia.org/wiki/Tokyo\t\t\t\t\t\t\n\
+             8\t2643743\tde\tLondon\t\t\t\t\t\t\n",
+        );
+        let mut gc = ReverseGeocoder::from_file("../cities.txt");
+        gc.load_alternate_names(&names, &["ja", "de", "ar", "link"])
+            .unwrap();
+        let (_, tokyo) = gc.search(35.69, 139.69, 1)[0];
+
+        assert_eq!(gc.localized_name(tokyo, &["ja"]), Some("東京"));
+        assert_eq!(gc.localized_name(tokyo, &["de-AT", "ja"]), Some("Tokio"));
+        assert_eq!(gc.localized_name(tokyo, &["AR"]), Some("طوكيو"));
+        assert_eq!(gc.localized_name(tokyo, &["fr", "link"]), None);
+        assert_eq!(gc.localized_name(tokyo, &["xx-unknown"]), None);
+        assert_eq!(gc.localized.len(), 3);
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{City, Float};
+
+/// Anything with a location that can be indexed by a [`ReverseGeocoder`].
+///
+/// [`City`] is the default payload, but own records like store locations or depots can be
+/// indexed as well. Searching by coordinates, radius, bounding box and name as well as snapshots
+/// work for all payloads, admin divisions and countries are only available for cities.
+///
+/// # Example
+/// ```rust
+/// use geocoder::{Float, Locatable, ReverseGeocoder};
+///
+/// struct Store {
+///     name: &'static str,
+///     lat: Float,
+///     lng: Float,
+/// }
+///
+/// impl Locatable for Store {
+///     fn coordinates(&self) -> (Float, Float) {
+///         (self.lat, self.lng)
+///     }
+/// }
+///
+/// let gc = ReverseGeocoder::new(vec![
+///     Store { name: "Cologne", lat: 50.94, lng: 6.96 },
+///     Store { name: "Berlin", lat: 52.52, lng: 13.40 },
+/// ]);
+/// let (_, store) = gc.search(51.08, 6.31, 1)[0];
+/// assert_eq!(store.name, "Cologne");
+/// ```
+///
+/// [`ReverseGeocoder`]: crate::ReverseGeocoder
+pub trait Locatable {
+    /// Latitude and longitude in decimal degrees (WGS84).
+    fn coordinates(&self) -> (Float, Float);
+
+    /// Names to find the record by, see [`ReverseGeocoder::find`](crate::ReverseGeocoder::find).
+    /// None by default.
+    fn indexed_names(&self) -> Vec<&str> {
+        Vec::new()
+    }
+
+    /// Unique id to look up the record by, see [`ReverseGeocoder::get`](crate::ReverseGeocoder::get).
+    /// None by default.
+    fn id(&self) -> Option<u32> {
+        None
+    }
+
+    /// Population to rank name search results by, see
+    /// [`ReverseGeocoder::find`](crate::ReverseGeocoder::find). None by default.
+    fn population(&self) -> Option<u32> {
+        None
+    }
+
+    /// GeoNames feature code to rank name search results with the same population by, e.g.
+    /// "PPLC". Empty by default.
+    fn feature_code(&self) -> &str {
+        ""
+    }
+
+    /// ISO-3166 2-letter country code to restrict autocompletion to, see
+    /// [`ReverseGeocoder::autocomplete`](crate::ReverseGeocoder::autocomplete). Empty by
+    /// default, so records without it never match a country.
+    fn country_code(&self) -> &str {
+        ""
+    }
+}
+
+impl Locatable for City {
+    fn coordinates(&self) -> (Float, Float) {
+        (self.latitude, self.longitude)
+    }
+
+    /// `name`, `asciiname` and all `alternatenames`.
+    fn indexed_names(&self) -> Vec<&str> {
+        [self.name(), self.asciiname()]
+            .into_iter()
+            .chain(self.alternatenames().split(','))
+            .filter(|name| !name.is_empty())
+            .collect()
+    }
+
+    /// The GeoNames id.
+    fn id(&self) -> Option<u32> {
+        Some(self.id)
+    }
+
+    fn population(&self) -> Option<u32> {
+        self.population
+    }
+
+    fn feature_code(&self) -> &str {
+        &self.feature_code
+    }
+
+    fn country_code(&self) -> &str {
+        &self.country_code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ReverseGeocoder;
+    use tracing_test::traced_test;
+
+    #[derive(Debug, PartialEq)]
+    struct Depot {
+        id: u32,
+        lat: Float,
+        lng: Float,
+    }
+
+    impl Locatable for Depot {
+        fn coordinates(&self) -> (Float, Float) {
+            (self.lat, self.lng)
+        }
+
+        fn id(&self) -> Option<u32> {
+            Some(self.id)
+        }
+    }
+
+    #[test]
+    #[traced_test]
+    fn searches_own_records() {
+        let depot = |id, lat, lng| Depot { id, lat, lng };
+        let gc = ReverseGeocoder::new(vec![
+            depot(1, 50.94, 6.96),
+            depot(2, 52.52, 13.40),
+            depot(3, 48.14, 11.58),
+        ]);
+        assert_eq!(format!("{}", gc), "ReverseGeocoder<cities=3, tree=3>");
+
+        let ids = |depots: Vec<&Depot>| depots.iter().map(|d| d.id).collect::<Vec<_>>();
+        let (distance, depot) = gc.search(51.08, 6.31, 1)[0];
+        assert_eq!(depot.id, 1);
+        assert_eq!((distance / 1000.0).round(), 48.0);
+        let nearby = gc.within_radius(51.0, 7.0, 100.0);
+        assert_eq!(ids(nearby.into_iter().map(|(_, d)| d).collect()), vec![1]);
+        let mut south = ids(gc.within_bbox(45.0, 5.0, 51.0, 15.0));
+        south.sort_unstable();
+        assert_eq!(south, vec![1, 3]);
+        assert_eq!(gc.get(2).map(|d| d.lat), Some(52.52));
+        assert_eq!(gc.get(4), None);
+
+        let even = gc.search_matching(51.08, 6.31, 5, None, |d| d.id % 2 == 0);
+        assert_eq!(ids(even.into_iter().map(|(_, d)| d).collect()), vec![2]);
+        assert!(gc
+            .search_matching(51.08, 6.31, 5, Some(100.0), |d| d.id % 2 == 0)
+            .is_empty());
+    }
+
+    struct Store {
+        name: &'static str,
+        country: &'static str,
+        visitors: u32,
+    }
+
+    impl Locatable for Store {
+        fn coordinates(&self) -> (Float, Float) {
+            (50.94, 6.96)
+        }
+
+        fn indexed_names(&self) -> Vec<&str> {
+            vec![self.name]
+        }
+
+        fn population(&self) -> Option<u32> {
+            Some(self.visitors)
+        }
+
+        fn country_code(&self) -> &str {
+            self.country
+        }
+    }
+
+    #[test]
+    #[traced_test]
+    fn finds_own_records_by_name() {
+        let store = |name, country, visitors| Store {
+            name,
+            country,
+            visitors,
+        };
+        let gc = ReverseGeocoder::new(vec![
+            store("Köln Hbf", "DE", 500),
+            store("Köln Messe", "DE", 2000),
+            store("Kolding", "DK", 100),
+        ]);
+        let names = |stores: Vec<&Store>| stores.iter().map(|s| s.name).collect::<Vec<_>>();
+
+        assert_eq!(names(gc.find("KÖLN HBF", 5)), vec!["Köln Hbf"]);
+        assert_eq!(names(gc.find_exact("Köln Hbf", 5)), vec!["Köln Hbf"]);
+        assert_eq!(
+            names(gc.autocomplete("kol", 5, None, None)),
+            vec!["Köln Messe", "Köln Hbf", "Kolding"]
+        );
+        assert_eq!(
+            names(gc.autocomplete("kol", 5, Some("DK"), None)),
+            vec!["Kolding"]
+        );
+    }
+}
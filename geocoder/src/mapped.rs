@@ -0,0 +1,819 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fmt::{Debug, Display, Formatter};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+
+use kiddo::float::distance::squared_euclidean;
+use memmap2::Mmap;
+
+use crate::snapshot::invalid_snapshot;
+use crate::{
+    degrees_lat_lng_to_unit_sphere, errors, kilometres_to_unit_sphere_squared_euclidean, to_f64,
+    BoundingBox, City, DistanceMethod, Filter, Float, Names, Properties, ReverseGeocoder,
+};
+
+/// Identifies a file as a memory-mapped snapshot.
+const MAGIC: &[u8; 8] = b"GEOCMMAP";
+
+/// Version of the file format, increased on every incompatible change.
+const VERSION: u32 = 1;
+
+/// Size of the header in bytes: magic, version, float size, padding and number of cities.
+const HEADER_LEN: usize = 8 + 4 + 1 + 3 + 8;
+
+const FLOAT_SIZE: usize = std::mem::size_of::<Float>();
+
+// Indices of the string fields of a record, see `CityRef`.
+const NAME: usize = 0;
+const ASCIINAME: usize = 1;
+const ALTERNATENAMES: usize = 2;
+const FEATURE_CLASS: usize = 3;
+const FEATURE_CODE: usize = 4;
+const COUNTRY_CODE: usize = 5;
+const CC2: usize = 6;
+const ADMIN1_CODE: usize = 7;
+const ADMIN2_CODE: usize = 8;
+const ADMIN3_CODE: usize = 9;
+const ADMIN4_CODE: usize = 10;
+const DEM: usize = 11;
+const TIMEZONE: usize = 12;
+const MODIFICATION_DATE: usize = 13;
+const STRING_FIELDS: usize = 14;
+
+const HAS_POPULATION: u8 = 1;
+const HAS_ELEVATION: u8 = 2;
+
+// Layout of a city record: id, population, elevation, flags, padding, latitude, longitude and
+// the (offset; length) of each string field in the string section.
+const ID: usize = 0;
+const POPULATION: usize = 4;
+const ELEVATION: usize = 8;
+const FLAGS: usize = 10;
+const LATITUDE: usize = 12;
+const LONGITUDE: usize = LATITUDE + FLOAT_SIZE;
+const STRINGS: usize = LONGITUDE + FLOAT_SIZE;
+const RECORD_LEN: usize = STRINGS + STRING_FIELDS * 8;
+
+/// Size of a tree node: ECEF coordinates and the index of the city.
+const NODE_LEN: usize = 3 * FLOAT_SIZE + 4;
+
+/// Read-only geocoder backed by a memory-mapped file, see [`ReverseGeocoder::save_mapped`].
+///
+/// Nothing is parsed or copied on startup: cities are fixed-size records pointing into a string
+/// section, and the search tree is stored as an implicit, balanced kd-tree. All processes mapping
+/// the same file share the operating system's page cache, so running many replicas on one node
+/// costs little extra memory. Cities are accessed through [`CityRef`] views borrowing from the
+/// mapped file.
+///
+/// # Example
+/// ```rust,no_run
+/// let gc = geocoder::MappedGeocoder::open("allCountries.mmap")?;
+/// let (distance, city) = gc.search(51.08, 6.31, 1)[0];
+/// println!("{} is {} m away", city.name(), distance);
+/// # Ok::<(), geocoder::errors::Error>(())
+/// ```
+pub struct MappedGeocoder {
+    mmap: Mmap,
+    len: usize,
+    distance_method: DistanceMethod,
+}
+
+/// Returns `true` if the file is a memory-mapped snapshot written by
+/// [`ReverseGeocoder::save_mapped`], see [`is_snapshot`](crate::is_snapshot).
+pub fn is_mapped(filename: &str) -> errors::Result<bool> {
+    let mut magic = [0u8; 8];
+    let mut file = File::open(filename)?;
+    match file.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == MAGIC),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+impl Display for MappedGeocoder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MappedGeocoder<cities={}>", self.len)
+    }
+}
+
+impl MappedGeocoder {
+    /// Map a file written by [`ReverseGeocoder::save_mapped`] into memory.
+    ///
+    /// Only the header is validated, there is no checksum as that would require reading the
+    /// whole file. The file must not be modified while it is mapped: replace it by renaming a
+    /// new file over it instead of writing to it in place.
+    pub fn open(filename: &str) -> errors::Result<MappedGeocoder> {
+        tracing::debug!("Mapping {}", filename);
+        let file = File::open(filename)?;
+        // SAFETY: the mapping is read-only, and the file isn't modified while it is mapped as
+        // documented above.
+        let mmap = unsafe { Mmap::map(&file)? };
+
+        if mmap.len() < HEADER_LEN || &mmap[0..8] != MAGIC {
+            return Err(invalid_snapshot(filename, "not a memory-mapped snapshot"));
+        }
+        let version = read_u32(&mmap, 8);
+        if version != VERSION {
+            return Err(invalid_snapshot(
+                filename,
+                format!("unsupported version {}, expected {}", version, VERSION),
+            ));
+        }
+        let float_size = mmap[12] as usize;
+        if float_size != FLOAT_SIZE {
+            return Err(invalid_snapshot(
+                filename,
+                format!(
+                    "written with {}-bit coordinates, expected {}-bit",
+                    float_size * 8,
+                    FLOAT_SIZE * 8
+                ),
+            ));
+        }
+        let len = u64::from_le_bytes(mmap[16..24].try_into().unwrap()) as usize;
+        let strings_start = len
+            .checked_mul(RECORD_LEN + NODE_LEN)
+            .and_then(|n| n.checked_add(HEADER_LEN));
+        if !matches!(strings_start, Some(start) if start <= mmap.len()) {
+            return Err(invalid_snapshot(filename, "file is truncated"));
+        }
+        tracing::info!("Mapped {} cities", len);
+
+        Ok(Self {
+            mmap,
+            len,
+            distance_method: DistanceMethod::default(),
+        })
+    }
+
+    /// Number of cities.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if there are no cities.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over all cities in the order of the original data file.
+    pub fn iter(&self) -> impl Iterator<Item = CityRef<'_>> {
+        (0..self.len).map(|idx| self.city(idx))
+    }
+
+    /// Set the formula used to calculate the distances returned by searches.
+    pub fn set_distance_method(&mut self, method: DistanceMethod) {
+        self.distance_method = method;
+    }
+
+    /// Finds the `results` cities nearest to the given coordinates (WGS84, decimal format).
+    ///
+    /// Returns a Vec of tuples consisting of the distance in metres to the given coordinates and
+    /// the found city, nearest first, like [`ReverseGeocoder::search`].
+    pub fn search(&self, lat: Float, lng: Float, results: usize) -> Vec<(f64, CityRef<'_>)> {
+        self.nearest(lat, lng, results, Float::INFINITY, |_| true)
+    }
+
+    /// Finds the `results` cities nearest to the given coordinates that match the filter, within
+    /// `max_distance_km` kilometres if given, like [`ReverseGeocoder::search_filtered`].
+    ///
+    /// The filter is checked while searching the tree, so no more cities are visited than
+    /// necessary to find the nearest matching ones.
+    pub fn search_filtered(
+        &self,
+        lat: Float,
+        lng: Float,
+        results: usize,
+        max_distance_km: Option<Float>,
+        filter: &Filter,
+    ) -> Vec<(f64, CityRef<'_>)> {
+        let max_distance = max_distance_km
+            .map(kilometres_to_unit_sphere_squared_euclidean)
+            .unwrap_or(Float::INFINITY);
+        self.nearest(lat, lng, results, max_distance, |city| {
+            filter.matches_ref(city)
+        })
+    }
+
+    /// Finds all cities within `km` kilometres (great-circle distance) of the given coordinates,
+    /// nearest first, like [`ReverseGeocoder::within_radius`].
+    pub fn within_radius(&self, lat: Float, lng: Float, km: Float) -> Vec<(f64, CityRef<'_>)> {
+        let query = degrees_lat_lng_to_unit_sphere(lat, lng);
+        let radius = kilometres_to_unit_sphere_squared_euclidean(km);
+        let mut found = vec![];
+        self.within(&query, radius, 0, self.len, 0, &mut found);
+        found.sort_unstable();
+        self.to_results(lat, lng, found)
+    }
+
+    /// Finds all cities within the given bounding box, like [`ReverseGeocoder::within_bbox`].
+    pub fn within_bbox(
+        &self,
+        min_lat: Float,
+        min_lng: Float,
+        max_lat: Float,
+        max_lng: Float,
+    ) -> Vec<CityRef<'_>> {
+        let bbox = BoundingBox {
+            min_lat,
+            min_lng,
+            max_lat,
+            max_lng,
+        };
+        let contains = |city: &CityRef| bbox.contains(city.latitude(), city.longitude());
+        if bbox.is_empty() {
+            return vec![];
+        }
+        let Some((center, radius)) = bbox.circle() else {
+            return self.iter().filter(contains).collect();
+        };
+        let mut found = vec![];
+        self.within(&center, radius, 0, self.len, 0, &mut found);
+        found
+            .into_iter()
+            .filter(|Candidate(_, idx)| *idx < self.len)
+            .map(|Candidate(_, idx)| self.city(idx))
+            .filter(contains)
+            .collect()
+    }
+
+    /// IANA timezone id of the city nearest to the given coordinates, which may be wrong close
+    /// to zone borders. See [`ReverseGeocoder::timezone`] for a lookup using boundaries.
+    pub fn timezone(&self, lat: Float, lng: Float) -> Option<&str> {
+        let (_, city) = self.search(lat, lng, 1).into_iter().next()?;
+        (!city.timezone().is_empty()).then_some(city.timezone())
+    }
+
+    fn nearest<F: Fn(&CityRef) -> bool>(
+        &self,
+        lat: Float,
+        lng: Float,
+        results: usize,
+        max_distance: Float,
+        keep: F,
+    ) -> Vec<(f64, CityRef<'_>)> {
+        if results == 0 {
+            return vec![];
+        }
+        let mut search = NearestSearch {
+            query: degrees_lat_lng_to_unit_sphere(lat, lng),
+            n: results,
+            max_distance,
+            keep,
+            heap: BinaryHeap::with_capacity(results.min(self.len) + 1),
+        };
+        self.nearest_n(&mut search, 0, self.len, 0);
+        self.to_results(lat, lng, search.heap.into_sorted_vec())
+    }
+
+    fn city(&self, idx: usize) -> CityRef<'_> {
+        let start = HEADER_LEN + idx * RECORD_LEN;
+        CityRef {
+            record: &self.mmap[start..start + RECORD_LEN],
+            strings: &self.mmap[HEADER_LEN + self.len * (RECORD_LEN + NODE_LEN)..],
+        }
+    }
+
+    fn node(&self, pos: usize) -> ([Float; 3], usize) {
+        let start = HEADER_LEN + self.len * RECORD_LEN + pos * NODE_LEN;
+        let node = &self.mmap[start..start + NODE_LEN];
+        let xyz = [
+            read_float(node, 0),
+            read_float(node, FLOAT_SIZE),
+            read_float(node, 2 * FLOAT_SIZE),
+        ];
+        (xyz, read_u32(node, 3 * FLOAT_SIZE) as usize)
+    }
+
+    /// Collect the `n` nodes nearest to the query that are kept by the search from the subtree
+    /// stored in `lo..hi`.
+    fn nearest_n<F: Fn(&CityRef) -> bool>(
+        &self,
+        search: &mut NearestSearch<F>,
+        lo: usize,
+        hi: usize,
+        depth: usize,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let (point, idx) = self.node(mid);
+        let distance = squared_euclidean(&search.query, &point);
+        // a corrupt file may point to cities that don't exist
+        if distance <= search.max_distance && idx < self.len && (search.keep)(&self.city(idx)) {
+            search.heap.push(Candidate(distance, idx));
+            if search.heap.len() > search.n {
+                search.heap.pop();
+            }
+        }
+
+        let diff = search.query[depth % 3] - point[depth % 3];
+        let (near, far) = if diff < 0.0 {
+            ((lo, mid), (mid + 1, hi))
+        } else {
+            ((mid + 1, hi), (lo, mid))
+        };
+        self.nearest_n(search, near.0, near.1, depth + 1);
+        let worst = match search.heap.peek() {
+            Some(worst) if search.heap.len() >= search.n => worst.0,
+            _ => search.max_distance,
+        };
+        if diff * diff <= worst {
+            self.nearest_n(search, far.0, far.1, depth + 1);
+        }
+    }
+
+    /// Collect all nodes within `radius` (squared euclidean) of `query` from the subtree stored
+    /// in `lo..hi`.
+    fn within(
+        &self,
+        query: &[Float; 3],
+        radius: Float,
+        lo: usize,
+        hi: usize,
+        depth: usize,
+        found: &mut Vec<Candidate>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let (point, idx) = self.node(mid);
+        let distance = squared_euclidean(query, &point);
+        if distance <= radius {
+            found.push(Candidate(distance, idx));
+        }
+
+        let diff = query[depth % 3] - point[depth % 3];
+        if diff <= 0.0 || diff * diff <= radius {
+            self.within(query, radius, lo, mid, depth + 1, found);
+        }
+        if diff >= 0.0 || diff * diff <= radius {
+            self.within(query, radius, mid + 1, hi, depth + 1, found);
+        }
+    }
+
+    #[allow(clippy::unnecessary_cast)] // Float is f64 with the "f64" feature
+    fn to_results(
+        &self,
+        lat: Float,
+        lng: Float,
+        candidates: Vec<Candidate>,
+    ) -> Vec<(f64, CityRef<'_>)> {
+        candidates
+            .into_iter()
+            // a corrupt file may point to cities that don't exist
+            .filter(|Candidate(_, idx)| *idx < self.len)
+            .map(|Candidate(_, idx)| {
+                let city = self.city(idx);
+                let distance = self.distance_method.distance(
+                    lat as f64,
+                    lng as f64,
+                    city.latitude() as f64,
+                    city.longitude() as f64,
+                );
+                (distance, city)
+            })
+            .collect()
+    }
+}
+
+/// State of a search for the `n` nearest cities within `max_distance` (squared euclidean) of
+/// `query` that are kept by the `keep` predicate.
+struct NearestSearch<F> {
+    query: [Float; 3],
+    n: usize,
+    max_distance: Float,
+    keep: F,
+    heap: BinaryHeap<Candidate>,
+}
+
+/// Squared euclidean distance and city index of a tree node, ordered by distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate(Float, usize);
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+/// Borrowed view of a city stored in a [`MappedGeocoder`].
+///
+/// Provides the same data as [`City`] without copying it out of the mapped file. Use
+/// `City::from` to get an owned copy.
+#[derive(Clone, Copy)]
+pub struct CityRef<'a> {
+    record: &'a [u8],
+    strings: &'a [u8],
+}
+
+macro_rules! string_fields {
+    ($($(#[$doc:meta])* $name:ident = $field:ident;)*) => {
+        $(
+            $(#[$doc])*
+            pub fn $name(&self) -> &'a str {
+                self.string($field)
+            }
+        )*
+    };
+}
+
+impl<'a> CityRef<'a> {
+    /// Integer id of record in geonames database
+    pub fn id(&self) -> u32 {
+        read_u32(self.record, ID)
+    }
+
+    /// Latitude in decimal degrees (wgs84)
+    pub fn latitude(&self) -> Float {
+        read_float(self.record, LATITUDE)
+    }
+
+    /// Longitude in decimal degrees (wgs84)
+    pub fn longitude(&self) -> Float {
+        read_float(self.record, LONGITUDE)
+    }
+
+    /// Population, if known
+    pub fn population(&self) -> Option<u32> {
+        (self.record[FLAGS] & HAS_POPULATION != 0).then(|| read_u32(self.record, POPULATION))
+    }
+
+    /// Elevation in metres, if known
+    pub fn elevation(&self) -> Option<i16> {
+        (self.record[FLAGS] & HAS_ELEVATION != 0)
+            .then(|| i16::from_le_bytes(self.record[ELEVATION..ELEVATION + 2].try_into().unwrap()))
+    }
+
+    string_fields! {
+        /// Name of geographical point (utf8)
+        name = NAME;
+        /// Name of geographical point in plain ascii characters
+        asciiname = ASCIINAME;
+        /// Alternate names, comma separated
+        alternatenames = ALTERNATENAMES;
+        /// Feature class, see http://www.geonames.org/export/codes.html
+        feature_class = FEATURE_CLASS;
+        /// Feature code, see http://www.geonames.org/export/codes.html
+        feature_code = FEATURE_CODE;
+        /// ISO-3166 2-letter country code
+        country_code = COUNTRY_CODE;
+        /// Alternate country codes, comma separated
+        cc2 = CC2;
+        /// Code of the first administrative division
+        admin1_code = ADMIN1_CODE;
+        /// Code of the second administrative division
+        admin2_code = ADMIN2_CODE;
+        /// Code of the third administrative division
+        admin3_code = ADMIN3_CODE;
+        /// Code of the fourth administrative division
+        admin4_code = ADMIN4_CODE;
+        /// Digital elevation model
+        dem = DEM;
+        /// IANA timezone id
+        timezone = TIMEZONE;
+        /// Date of last modification in yyyy-MM-dd format
+        modification_date = MODIFICATION_DATE;
+    }
+
+    /// Get coordinates as ECEF (x;y;z)-coordinates.
+    pub fn as_xyz(&self) -> [Float; 3] {
+        degrees_lat_lng_to_unit_sphere(self.latitude(), self.longitude())
+    }
+
+    /// Get (latitude; longitude) as f64, see [`City::lat_lng`].
+    pub fn lat_lng(&self) -> (f64, f64) {
+        (to_f64(self.latitude()), to_f64(self.longitude()))
+    }
+
+    /// Returns an empty string if the file is corrupt, as the strings aren't validated on startup.
+    fn string(&self, field: usize) -> &'a str {
+        let pos = STRINGS + field * 8;
+        let start = read_u32(self.record, pos) as usize;
+        let len = read_u32(self.record, pos + 4) as usize;
+        self.strings
+            .get(start..start + len)
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+            .unwrap_or_default()
+    }
+}
+
+impl Debug for CityRef<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CityRef")
+            .field("id", &self.id())
+            .field("name", &self.name())
+            .finish()
+    }
+}
+
+impl Display for CityRef<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}, {}", self.name(), self.country_code())
+    }
+}
+
+impl From<CityRef<'_>> for City {
+    fn from(city: CityRef<'_>) -> Self {
+        City {
+            id: city.id(),
+            names: Names::new(city.name(), city.asciiname(), city.alternatenames()),
+            latitude: city.latitude(),
+            longitude: city.longitude(),
+            feature_class: city.feature_class().into(),
+            feature_code: city.feature_code().into(),
+            country_code: city.country_code().into(),
+            cc2: city.cc2().into(),
+            admin1_code: city.admin1_code().into(),
+            admin2_code: city.admin2_code().into(),
+            admin3_code: city.admin3_code().into(),
+            admin4_code: city.admin4_code().into(),
+            population: city.population(),
+            elevation: city.elevation(),
+            dem: city.dem().into(),
+            timezone: city.timezone().into(),
+            modification_date: city.modification_date().into(),
+            // Not stored in mapped snapshots
+            properties: Properties::default(),
+        }
+    }
+}
+
+impl ReverseGeocoder {
+    /// Write the cities to a file that can be memory-mapped by [`MappedGeocoder::open`].
+    ///
+    /// Only cities are written: names, admin codes and country info aren't supported by
+    /// [`MappedGeocoder`]. Files written with the `f64` feature can only be mapped with the
+    /// `f64` feature and vice versa.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// let gc = geocoder::ReverseGeocoder::from_file("allCountries.txt");
+    /// gc.save_mapped("allCountries.mmap")?;
+    /// # Ok::<(), geocoder::errors::Error>(())
+    /// ```
+    pub fn save_mapped(&self, filename: &str) -> errors::Result<()> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&[FLOAT_SIZE as u8, 0, 0, 0])?;
+        writer.write_all(&(self.cities.len() as u64).to_le_bytes())?;
+
+        let mut strings: Vec<u8> = vec![];
+        let mut record = Vec::with_capacity(RECORD_LEN);
+        for city in &self.cities {
+            let flags = if city.population.is_some() {
+                HAS_POPULATION
+            } else {
+                0
+            } | if city.elevation.is_some() {
+                HAS_ELEVATION
+            } else {
+                0
+            };
+            record.clear();
+            record.extend(city.id.to_le_bytes());
+            record.extend(city.population.unwrap_or(0).to_le_bytes());
+            record.extend(city.elevation.unwrap_or(0).to_le_bytes());
+            record.extend([flags, 0]);
+            record.extend(city.latitude.to_le_bytes());
+            record.extend(city.longitude.to_le_bytes());
+            for value in [
+                city.name(),
+                city.asciiname(),
+                city.alternatenames(),
+                &city.feature_class,
+                &city.feature_code,
+                &city.country_code,
+                &city.cc2,
+                &city.admin1_code,
+                &city.admin2_code,
+                &city.admin3_code,
+                &city.admin4_code,
+                &city.dem,
+                &city.timezone,
+                &city.modification_date,
+            ] {
+                let start = u32::try_from(strings.len())
+                    .map_err(|_| invalid_snapshot(filename, "more than 4 GiB of strings"))?;
+                record.extend(start.to_le_bytes());
+                record.extend((value.len() as u32).to_le_bytes());
+                strings.extend(value.as_bytes());
+            }
+            writer.write_all(&record)?;
+        }
+
+        let mut nodes: Vec<([Float; 3], u32)> = self
+            .cities
+            .iter()
+            .enumerate()
+            .map(|(idx, city)| (city.as_xyz(), idx as u32))
+            .collect();
+        build_tree(&mut nodes, 0);
+        for (xyz, idx) in nodes {
+            for coordinate in xyz {
+                writer.write_all(&coordinate.to_le_bytes())?;
+            }
+            writer.write_all(&idx.to_le_bytes())?;
+        }
+
+        writer.write_all(&strings)?;
+        writer.flush()?;
+        tracing::info!(
+            "Saved memory-mapped snapshot of {} cities to {}",
+            self.cities.len(),
+            filename
+        );
+        Ok(())
+    }
+}
+
+/// Arrange the nodes as an implicit kd-tree: the median of each range along the splitting axis
+/// is stored in the middle, smaller values before and larger values after it.
+fn build_tree(nodes: &mut [([Float; 3], u32)], depth: usize) {
+    if nodes.len() <= 1 {
+        return;
+    }
+    let mid = nodes.len() / 2;
+    let axis = depth % 3;
+    nodes.select_nth_unstable_by(mid, |a, b| a.0[axis].total_cmp(&b.0[axis]));
+    let (left, right) = nodes.split_at_mut(mid);
+    build_tree(left, depth + 1);
+    build_tree(&mut right[1..], depth + 1);
+}
+
+fn read_u32(bytes: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap())
+}
+
+fn read_float(bytes: &[u8], pos: usize) -> Float {
+    Float::from_le_bytes(bytes[pos..pos + FLOAT_SIZE].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Error;
+    use crate::tests::write_test_file;
+    use tracing_test::traced_test;
+
+    fn mapped(name: &str, gc: &ReverseGeocoder) -> MappedGeocoder {
+        let path = write_test_file(name, "");
+        gc.save_mapped(&path).unwrap();
+        MappedGeocoder::open(&path).unwrap()
+    }
+
+    //noinspection SpellCheckingInspection
+    #[test]
+    #[traced_test]
+    fn maps_cities_from_file() {
+        let gc = ReverseGeocoder::from_file("../cities.txt");
+        let path = write_test_file("cities.mmap", "");
+        gc.save_mapped(&path).unwrap();
+        assert!(is_mapped(&path).unwrap());
+        assert!(!is_mapped("../cities.txt").unwrap());
+        let mapped = MappedGeocoder::open(&path).unwrap();
+        assert_eq!(mapped.len(), 11);
+
+        let (d, city) = mapped.search(50.88, 6.92, 1)[0];
+        assert_eq!(d.round(), 47791.0);
+        assert_eq!(format!("{}", city), "Erkelenz, DE");
+        let (_, expected) = gc.search(50.88, 6.92, 1)[0];
+        let city = City::from(city);
+        assert_eq!(city.alternatenames(), expected.alternatenames());
+        assert_eq!(city.population, expected.population);
+        assert_eq!(city.elevation, expected.elevation);
+        assert_eq!(city.lat_lng(), expected.lat_lng());
+        assert_eq!(city.modification_date, expected.modification_date);
+
+        let ids = |results: Vec<(f64, CityRef)>| -> Vec<u32> {
+            results.iter().map(|(_, c)| c.id()).collect()
+        };
+        assert_eq!(
+            ids(mapped.within_radius(35.0, 137.0, 500.0)),
+            vec![1853909, 1850147]
+        );
+        assert_eq!(mapped.within_radius(0.0, 0.0, 30000.0).len(), 11);
+    }
+
+    #[test]
+    #[traced_test]
+    fn finds_same_neighbours_as_kdtree() {
+        use rand::{Rng, SeedableRng};
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let cities: Vec<City> = (0..2000)
+            .map(|id| City {
+                id,
+                latitude: rng.gen_range(-90.0..90.0),
+                longitude: rng.gen_range(-180.0..180.0),
+                ..Default::default()
+            })
+            .collect();
+        let gc = ReverseGeocoder::new(cities);
+        let mapped = mapped("random.mmap", &gc);
+
+        for _ in 0..100 {
+            let (lat, lng) = (rng.gen_range(-90.0..90.0), rng.gen_range(-180.0..180.0));
+            let expected: Vec<u32> = gc.search(lat, lng, 5).iter().map(|(_, c)| c.id).collect();
+            let found: Vec<u32> = mapped
+                .search(lat, lng, 5)
+                .iter()
+                .map(|(_, c)| c.id())
+                .collect();
+            assert_eq!(found, expected);
+
+            let expected = gc.within_radius(lat, lng, 1000.0).len();
+            assert_eq!(mapped.within_radius(lat, lng, 1000.0).len(), expected);
+        }
+    }
+
+    #[test]
+    #[traced_test]
+    fn filters_like_reverse_geocoder() {
+        let gc = ReverseGeocoder::from_file("../cities.txt");
+        let mapped = mapped("filtered.mmap", &gc);
+        let ids = |results: Vec<(f64, CityRef)>| -> Vec<u32> {
+            results.iter().map(|(_, c)| c.id()).collect()
+        };
+
+        let filter = Filter {
+            country_codes: vec![String::from("JP")],
+            ..Default::default()
+        };
+        let expected: Vec<u32> = gc
+            .search_filtered(50.93, 6.95, 5, None, &filter)
+            .iter()
+            .map(|(_, c)| c.id)
+            .collect();
+        assert_eq!(expected.len(), 2);
+        assert_eq!(
+            ids(mapped.search_filtered(50.93, 6.95, 5, None, &filter)),
+            expected
+        );
+
+        assert!(mapped
+            .search_filtered(50.93, 6.95, 5, Some(1000.0), &filter)
+            .is_empty());
+        assert!(mapped.search(50.93, 6.95, 0).is_empty());
+        assert_eq!(mapped.timezone(50.93, 6.95), Some("Europe/Berlin"));
+    }
+
+    #[test]
+    #[traced_test]
+    fn finds_same_cities_in_bbox_as_kdtree() {
+        let gc = ReverseGeocoder::from_file("../cities.txt");
+        let mapped = mapped("bbox.mmap", &gc);
+        let boxes = [
+            (30.0, 130.0, 40.0, 140.0),
+            (50.0, 6.0, 52.0, 8.0),
+            (-90.0, 170.0, 90.0, -170.0),
+            (-90.0, -180.0, 90.0, 180.0),
+            (40.0, 0.0, 30.0, 10.0),
+        ];
+        for (min_lat, min_lng, max_lat, max_lng) in boxes {
+            let mut expected: Vec<u32> = gc
+                .within_bbox(min_lat, min_lng, max_lat, max_lng)
+                .iter()
+                .map(|c| c.id)
+                .collect();
+            let mut found: Vec<u32> = mapped
+                .within_bbox(min_lat, min_lng, max_lat, max_lng)
+                .iter()
+                .map(|c| c.id())
+                .collect();
+            expected.sort_unstable();
+            found.sort_unstable();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    #[traced_test]
+    fn rejects_other_files() {
+        match MappedGeocoder::open("../cities.txt") {
+            Err(Error::InvalidSnapshot { reason, .. }) => {
+                assert_eq!(reason, "not a memory-mapped snapshot")
+            }
+            _ => panic!("expected an error"),
+        }
+
+        let path = write_test_file("truncated.mmap", "");
+        ReverseGeocoder::from_file("../cities.txt")
+            .save_mapped(&path)
+            .unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..HEADER_LEN + RECORD_LEN]).unwrap();
+        assert!(MappedGeocoder::open(&path).is_err());
+    }
+}
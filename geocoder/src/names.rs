@@ -0,0 +1,612 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{
+    degrees_lat_lng_to_unit_sphere, kilometres_between, Float, Locatable, ReverseGeocoder,
+};
+
+/// Distance at which the location bias halves a city's score in autocomplete results.
+const BIAS_SCALE_IN_KM: Float = 100.0;
+
+/// Number of consecutive entries of the [`NameIndex`] that share a maximum population.
+const BLOCK_LEN: usize = 64;
+
+/// Sorted list of normalized names and the index of the city they belong to.
+///
+/// Each city is listed under its `name`, `asciiname` and all of its `alternatenames`, other
+/// payloads under their [`Locatable::indexed_names`]. As the list is sorted, all names sharing a
+/// prefix form a contiguous range.
+///
+/// The names are concatenated into a single string, each one terminated by a `'\0'`, so an
+/// entry only takes 8 bytes instead of a `String` and a `usize`. Names shared by several cities
+/// are stored once when the index is built. The space of names dropped by an update is reclaimed
+/// once it is half of the string.
+///
+/// To find the most populous cities with a prefix without scanning all of its entries, the
+/// maximum population of every block of [`BLOCK_LEN`] entries is kept in a binary tree.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct NameIndex {
+    keys: String,
+    entries: Vec<Entry>,
+    /// Upper bound of the bytes of `keys` that no entry refers to anymore
+    unused: usize,
+    /// Maximum population of each node, the children of node `n` are `2n` and `2n + 1`. The
+    /// blocks are the leaves, starting at the first power of two not less than their number.
+    populations: Vec<u32>,
+}
+
+/// Name in the [`NameIndex`], as the offset of its `keys`, and the index of its city.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+struct Entry {
+    start: u32,
+    idx: u32,
+}
+
+impl NameIndex {
+    pub(crate) fn new<T: Locatable>(items: &[T]) -> NameIndex {
+        let mut names: Vec<(String, usize)> = vec![];
+        for (idx, item) in items.iter().enumerate() {
+            names.extend(keys(item).into_iter().map(|key| (key, idx)));
+        }
+        names.sort_unstable();
+        tracing::info!("Indexed {} names", names.len());
+
+        let mut index = NameIndex {
+            entries: Vec::with_capacity(names.len()),
+            ..Default::default()
+        };
+        let mut previous: Option<Entry> = None;
+        for (key, idx) in names {
+            let entry = match previous {
+                Some(entry) if index.key(&entry) == key => Entry {
+                    idx: to_u32(idx),
+                    ..entry
+                },
+                _ => index.push(&key, idx),
+            };
+            index.entries.push(entry);
+            previous = Some(entry);
+        }
+        index.keys.shrink_to_fit();
+        index.index_populations(items);
+        index
+    }
+
+    /// Update the index after cities were changed, in time linear in the number of entries.
+    ///
+    /// Drops the entries of the `removed` indices, which refer to the cities before the change,
+    /// moves the entries of cities whose index changed from `moved` keys to their values, and
+    /// indexes the `added` indices of `items` afresh. Only the new entries are sorted, then they
+    /// are merged into the sorted list from its end.
+    pub(crate) fn update<T: Locatable>(
+        &mut self,
+        items: &[T],
+        removed: &HashSet<usize>,
+        moved: &HashMap<usize, usize>,
+        added: &HashSet<usize>,
+    ) {
+        if !removed.is_empty() || !moved.is_empty() {
+            let mut unused = 0;
+            self.entries.retain_mut(|entry| {
+                if removed.contains(&(entry.idx as usize)) {
+                    unused += self.keys[entry.start as usize..].find('\0').unwrap_or(0) + 1;
+                    return false;
+                }
+                if let Some(new_idx) = moved.get(&(entry.idx as usize)) {
+                    entry.idx = to_u32(*new_idx);
+                }
+                true
+            });
+            self.unused += unused;
+        }
+
+        let mut new: Vec<Entry> = vec![];
+        for idx in added {
+            for key in keys(&items[*idx]) {
+                new.push(self.push(&key, *idx));
+            }
+        }
+        new.sort_unstable_by(|a, b| self.key(a).cmp(self.key(b)).then(a.idx.cmp(&b.idx)));
+        // Merge from the end, so only entries after the first new one are moved. `i` and `j`
+        // are the numbers of old and new entries left, `i + j` the end of the unmerged part.
+        let (mut i, mut j) = (self.entries.len(), new.len());
+        self.entries.resize(i + j, Entry::default());
+        while j > 0 {
+            if i > 0 && self.key(&self.entries[i - 1]) > self.key(&new[j - 1]) {
+                self.entries.swap(i - 1, i + j - 1);
+                i -= 1;
+            } else {
+                self.entries[i + j - 1] = new[j - 1];
+                j -= 1;
+            }
+        }
+
+        if self.unused > self.keys.len() / 2 {
+            self.compact();
+        }
+        self.index_populations(items);
+    }
+
+    /// Indices of all cities with a name equal to the normalized `key`.
+    pub(crate) fn get(&self, key: &str) -> impl Iterator<Item = usize> + '_ {
+        let start = self.entries.partition_point(|e| self.key(e) < key);
+        let end = self.entries.partition_point(|e| self.key(e) <= key);
+        self.entries[start..end].iter().map(|e| e.idx as usize)
+    }
+
+    /// Visit the indices of the cities with a name starting with the normalized `prefix`, the
+    /// blocks of entries with the most populous cities first.
+    ///
+    /// `visit` gets the maximum population of the block along with each index, which is an
+    /// upper bound for all cities visited later, and returns `false` to stop. A city is visited
+    /// once for every matching name.
+    pub(crate) fn prefixed_by_population<F>(&self, prefix: &str, mut visit: F)
+    where
+        F: FnMut(u32, usize) -> bool,
+    {
+        let start = self.entries.partition_point(|e| self.key(e) < prefix);
+        let len = self.entries[start..].partition_point(|e| self.key(e).starts_with(prefix));
+        let range = start..start + len;
+        if range.is_empty() {
+            return;
+        }
+
+        let leaves = self.populations.len() / 2;
+        let mut heap = BinaryHeap::from([(self.populations[1], 1_usize)]);
+        while let Some((population, node)) = heap.pop() {
+            // Entries of the blocks below the node
+            let depth = node.ilog2();
+            let width = leaves >> depth;
+            let first = (node - (1 << depth)) * width * BLOCK_LEN;
+            let entries = first.max(range.start)..(first + width * BLOCK_LEN).min(range.end);
+            if entries.is_empty() {
+                continue;
+            }
+            if node >= leaves {
+                for entry in &self.entries[entries] {
+                    if !visit(population, entry.idx as usize) {
+                        return;
+                    }
+                }
+            } else {
+                heap.extend([2 * node, 2 * node + 1].map(|n| (self.populations[n], n)));
+            }
+        }
+    }
+
+    fn key(&self, entry: &Entry) -> &str {
+        key_at(&self.keys, entry.start)
+    }
+
+    /// Append `key` to the names and return an entry for it, which isn't in the list yet.
+    fn push(&mut self, key: &str, idx: usize) -> Entry {
+        let entry = Entry {
+            start: to_u32(self.keys.len()),
+            idx: to_u32(idx),
+        };
+        self.keys.push_str(key);
+        self.keys.push('\0');
+        entry
+    }
+
+    /// Compute the maximum population of all blocks and the nodes above them.
+    fn index_populations<T: Locatable>(&mut self, items: &[T]) {
+        let blocks = self.entries.chunks(BLOCK_LEN).len();
+        let leaves = blocks.next_power_of_two();
+        let mut populations = vec![0; 2 * leaves];
+        for (block, entries) in self.entries.chunks(BLOCK_LEN).enumerate() {
+            populations[leaves + block] = entries
+                .iter()
+                .map(|e| items[e.idx as usize].population().unwrap_or(0))
+                .max()
+                .unwrap_or(0);
+        }
+        for node in (1..leaves).rev() {
+            populations[node] = populations[2 * node].max(populations[2 * node + 1]);
+        }
+        self.populations = populations;
+    }
+
+    /// Copy the names that are still referred to into a new string, in the order of the list.
+    fn compact(&mut self) {
+        let mut keys = String::with_capacity(self.keys.len() - self.unused.min(self.keys.len()));
+        let mut previous: Option<(&str, u32)> = None;
+        for entry in &mut self.entries {
+            let key = key_at(&self.keys, entry.start);
+            entry.start = match previous {
+                Some((previous_key, start)) if previous_key == key => start,
+                _ => {
+                    let start = to_u32(keys.len());
+                    keys.push_str(key);
+                    keys.push('\0');
+                    start
+                }
+            };
+            previous = Some((key, entry.start));
+        }
+        self.keys = keys;
+        self.unused = 0;
+    }
+}
+
+/// Name starting at `start` of the concatenated `keys` of a [`NameIndex`].
+fn key_at(keys: &str, start: u32) -> &str {
+    let key = &keys[start as usize..];
+    &key[..key.find('\0').unwrap_or(key.len())]
+}
+
+/// Offset or index in the [`NameIndex`], which holds at most 4 GiB of names and 2^32 cities.
+fn to_u32(n: usize) -> u32 {
+    u32::try_from(n).expect("too many names for the name index")
+}
+
+/// Distinct normalized names of an item.
+fn keys<T: Locatable>(item: &T) -> Vec<String> {
+    let mut keys: Vec<String> = item.indexed_names().into_iter().map(normalize).collect();
+    keys.sort_unstable();
+    keys.dedup();
+    keys
+}
+
+/// Normalize a name for case and diacritic insensitive comparison.
+///
+/// Decomposes the name (NFD), drops combining marks and converts it to lower case, so that
+/// e.g. "Köln" and "KOLN" both become "koln". Drops `'\0'`, which ends names in the index.
+pub(crate) fn normalize(name: &str) -> String {
+    name.nfd()
+        .filter(|c| !is_combining_mark(*c) && *c != '\0')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Rank of a feature code, lower is more important.
+///
+/// See http://www.geonames.org/export/codes.html
+fn feature_rank<T: Locatable>(item: &T) -> u8 {
+    match item.feature_code() {
+        "PPLC" => 0,
+        "PPLG" => 1,
+        "PPLA" => 2,
+        "PPLA2" => 3,
+        "PPLA3" => 4,
+        "PPLA4" | "PPLA5" => 5,
+        "PPL" => 6,
+        _ => 7,
+    }
+}
+
+/// Order records by descending population, then by the importance of their feature code.
+pub(crate) fn rank<T: Locatable>(items: &mut [&T]) {
+    items.sort_by_key(|item| (Reverse(item.population().unwrap_or(0)), feature_rank(*item)));
+}
+
+impl<T: Locatable> ReverseGeocoder<T> {
+    /// Finds up to `results` cities by name, ignoring case and diacritics.
+    ///
+    /// All [`Locatable::indexed_names`] are searched, for cities `name`, `asciiname` and
+    /// `alternatenames`. Results are ranked by population and feature code.
+    ///
+    /// # Example
+    /// ```rust
+    /// # let gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// let results = gc.find("sao paulo", 1);
+    /// assert_eq!(results[0].name(), "São Paulo");
+    /// ```
+    pub fn find(&self, name: &str, results: usize) -> Vec<&T> {
+        self.find_matching(name, results, |_| true)
+    }
+
+    /// Finds up to `results` cities whose name exactly matches `name`.
+    ///
+    /// All [`Locatable::indexed_names`] are searched. Results are ranked by population and
+    /// feature code.
+    ///
+    /// # Example
+    /// ```rust
+    /// # let gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// assert!(gc.find_exact("sao paulo", 1).is_empty());
+    /// assert_eq!(gc.find_exact("São Paulo", 1)[0].id, 3448439);
+    /// ```
+    pub fn find_exact(&self, name: &str, results: usize) -> Vec<&T> {
+        self.find_matching(name, results, |city| city.indexed_names().contains(&name))
+    }
+
+    /// Finds up to `results` cities with a name starting with `prefix`, for type-ahead search.
+    ///
+    /// Case and diacritics are ignored. Results are ranked by population. If `country_code` is
+    /// given, only cities in that country are returned. If `near` is given as (lat, lng), the
+    /// population of each city is discounted by its distance to that point, so nearby places
+    /// are preferred. The names are looked at in blocks, the ones with the most populous cities
+    /// first, so a short prefix doesn't mean scoring every name that starts with it.
+    ///
+    /// # Example
+    /// ```rust
+    /// # let gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// let results = gc.autocomplete("sha", 5, None, None);
+    /// assert_eq!(results[0].name(), "Shanghai");
+    /// ```
+    pub fn autocomplete(
+        &self,
+        prefix: &str,
+        results: usize,
+        country_code: Option<&str>,
+        near: Option<(Float, Float)>,
+    ) -> Vec<&T> {
+        tracing::debug!("Autocompleting {} cities for {}", results, prefix);
+
+        let prefix = normalize(prefix);
+        if prefix.is_empty() || results == 0 {
+            return vec![];
+        }
+
+        let origin = near.map(|(lat, lng)| degrees_lat_lng_to_unit_sphere(lat, lng));
+        let by_score = |(a, x): &(Float, &T), (b, y): &(Float, &T)| {
+            b.partial_cmp(a)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| feature_rank(*x).cmp(&feature_rank(*y)))
+        };
+        // The best candidates so far, ordered by score. A score is at most the population, so
+        // once a block can't beat the last candidate, neither can any block after it.
+        let mut candidates: Vec<(Float, &T)> = vec![];
+        self.names
+            .prefixed_by_population(&prefix, |max_population, idx| {
+                let full = candidates.len() == results;
+                if full && candidates[results - 1].0 > max_population as Float {
+                    return false;
+                }
+                let city = &self.cities[idx];
+                if country_code.is_some() && country_code != Some(city.country_code()) {
+                    return true;
+                }
+                let mut score = city.population().unwrap_or(0) as Float;
+                if let Some(origin) = &origin {
+                    let (lat, lng) = city.coordinates();
+                    let xyz = degrees_lat_lng_to_unit_sphere(lat, lng);
+                    score /= 1.0 + kilometres_between(origin, &xyz) / BIAS_SCALE_IN_KM;
+                }
+                let candidate = (score, city);
+                if full && by_score(&candidate, &candidates[results - 1]) != Ordering::Less {
+                    return true;
+                }
+                // A city is visited once for each of its matching names
+                if candidates.iter().any(|(_, c)| std::ptr::eq(*c, city)) {
+                    return true;
+                }
+                let pos =
+                    candidates.partition_point(|c| by_score(c, &candidate) != Ordering::Greater);
+                candidates.insert(pos, candidate);
+                candidates.truncate(results);
+                true
+            });
+        candidates.into_iter().map(|(_, city)| city).collect()
+    }
+
+    fn find_matching<P>(&self, name: &str, results: usize, predicate: P) -> Vec<&T>
+    where
+        P: Fn(&T) -> bool,
+    {
+        tracing::debug!("Searching for {} cities named {}", results, name);
+
+        let mut cities: Vec<&T> = self
+            .names
+            .get(&normalize(name))
+            .map(|idx| &self.cities[idx])
+            .filter(|city| predicate(city))
+            .collect();
+        rank(&mut cities);
+        cities.truncate(results);
+        cities
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{City, Names};
+    use tracing_test::traced_test;
+
+    #[test]
+    fn merges_updated_names_into_sorted_index() {
+        let city = |name: &str| City {
+            names: Names::from(name),
+            ..Default::default()
+        };
+        let mut cities = vec![city("Bonn"), city("Essen"), city("Köln")];
+        let mut index = NameIndex::new(&cities);
+
+        // Essen is removed, Köln takes its place, Aachen and Düren are new
+        cities.swap_remove(1);
+        cities.extend([city("Zülpich"), city("Aachen"), city("Düren")]);
+        let removed = HashSet::from([1]);
+        let moved = HashMap::from([(2, 1)]);
+        let added = HashSet::from([2, 3, 4]);
+        index.update(&cities, &removed, &moved, &added);
+
+        let entries: Vec<(&str, usize)> = index
+            .entries
+            .iter()
+            .map(|entry| (index.key(entry), entry.idx as usize))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("aachen", 3),
+                ("bonn", 0),
+                ("duren", 4),
+                ("koln", 1),
+                ("zulpich", 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn stores_shared_names_once_and_reclaims_removed_ones() {
+        let city = |name: &str| City {
+            names: Names::from(name),
+            ..Default::default()
+        };
+        let mut cities = vec![city("Springfield"), city("Bonn"), city("Springfield")];
+        let mut index = NameIndex::new(&cities);
+        assert_eq!(index.keys, "bonn\0springfield\0");
+
+        cities[1] = city("Essen");
+        index.update(
+            &cities,
+            &HashSet::from([1]),
+            &HashMap::new(),
+            &HashSet::from([1]),
+        );
+        assert_eq!(index.keys, "bonn\0springfield\0essen\0");
+        cities[0] = city("Aachen");
+        cities[2] = city("Düren");
+        let changed = HashSet::from([0, 2]);
+        index.update(&cities, &changed, &HashMap::new(), &changed);
+        assert_eq!(index.keys, "aachen\0duren\0essen\0");
+        assert_eq!(index.get("duren").collect::<Vec<_>>(), vec![2]);
+        assert_eq!(index.get("springfield").count(), 0);
+    }
+
+    #[test]
+    fn normalizes_case_and_diacritics() {
+        assert_eq!(normalize("Köln"), "koln");
+        assert_eq!(normalize("SÃO PAULO"), "sao paulo");
+        assert_eq!(normalize("東京"), "東京");
+    }
+
+    //noinspection SpellCheckingInspection
+    #[test]
+    #[traced_test]
+    fn finds_cities_by_alternate_name() {
+        let gc = ReverseGeocoder::from_file("../cities.txt");
+        assert_eq!(gc.find("PEKING", 5)[0].name(), "Beijing");
+        assert_eq!(gc.find("東京", 5)[0].name(), "Tokyo");
+        assert!(gc.find("Atlantis", 5).is_empty());
+    }
+
+    #[test]
+    #[traced_test]
+    fn ranks_by_population_and_feature_code() {
+        let city = |id, feature_code: &str, population| City {
+            id,
+            names: Names::from("Springfield"),
+            feature_code: feature_code.into(),
+            population,
+            ..Default::default()
+        };
+        let gc = ReverseGeocoder::new(vec![
+            city(1, "PPL", Some(1000)),
+            city(2, "PPLA2", Some(150000)),
+            city(3, "PPLA", Some(1000)),
+            city(4, "PPLX", None),
+        ]);
+        let ids: Vec<u32> = gc.find("springfield", 10).iter().map(|c| c.id).collect();
+        assert_eq!(ids, vec![2, 3, 1, 4]);
+        assert_eq!(gc.find("springfield", 2).len(), 2);
+    }
+
+    //noinspection SpellCheckingInspection
+    #[test]
+    #[traced_test]
+    fn autocompletes_prefixes() {
+        let gc = ReverseGeocoder::from_file("../cities.txt");
+        let names = |results: Vec<&City>| {
+            results
+                .iter()
+                .map(|c| c.name().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(gc.autocomplete("d", 3, None, None)),
+            vec!["Mumbai", "Dhaka", "Tokyo"]
+        );
+        assert_eq!(
+            names(gc.autocomplete("D", 5, Some("JP"), None)),
+            vec!["Tokyo", "Osaka"]
+        );
+        assert!(gc.autocomplete("d", 5, Some("DE"), None).is_empty());
+        assert!(gc.autocomplete("", 5, None, None).is_empty());
+    }
+
+    #[test]
+    #[traced_test]
+    fn autocompletes_most_populous_of_many_cities() {
+        let cities: Vec<City> = (0..2000)
+            .map(|id| City {
+                id,
+                names: Names::from(format!("Town {}", id).as_str()),
+                population: Some(id * 7919 % 10007),
+                country_code: if id % 3 == 0 { "DE" } else { "NL" }.into(),
+                ..Default::default()
+            })
+            .collect();
+        let mut gc = ReverseGeocoder::new(cities.clone());
+        let expected = |prefix: &str, country_code: Option<&str>| {
+            let mut found: Vec<&City> = cities
+                .iter()
+                .filter(|c| normalize(c.name()).starts_with(prefix))
+                .filter(|c| country_code.is_none() || country_code == Some(c.country_code()))
+                .collect();
+            rank(&mut found);
+            found.iter().take(5).map(|c| c.id).collect::<Vec<_>>()
+        };
+        let ids = |results: Vec<&City>| results.iter().map(|c| c.id).collect::<Vec<_>>();
+
+        for prefix in ["t", "town 1", "town 19", "town 1999"] {
+            assert_eq!(
+                ids(gc.autocomplete(prefix, 5, None, None)),
+                expected(prefix, None)
+            );
+            assert_eq!(
+                ids(gc.autocomplete(prefix, 5, Some("DE"), None)),
+                expected(prefix, Some("DE"))
+            );
+        }
+
+        // Blocks are visited by descending population, starting with the most populous city
+        let mut bounds = vec![];
+        gc.names
+            .prefixed_by_population("town", |max_population, _| {
+                bounds.push(max_population);
+                true
+            });
+        assert_eq!(bounds.len(), 2000);
+        assert_eq!(
+            bounds[0],
+            cities.iter().filter_map(|c| c.population).max().unwrap()
+        );
+        assert!(bounds.windows(2).all(|w| w[0] >= w[1]));
+
+        gc.insert(City {
+            id: 5000,
+            names: Names::from("Town Hall"),
+            population: Some(20000),
+            ..Default::default()
+        });
+        assert_eq!(gc.autocomplete("town", 1, None, None)[0].id, 5000);
+    }
+
+    #[test]
+    #[traced_test]
+    fn autocomplete_prefers_nearby_places() {
+        let city = |id, lat, lng, population| City {
+            id,
+            names: Names::from(format!("Neustadt {}", id).as_str()),
+            latitude: lat,
+            longitude: lng,
+            population: Some(population),
+            ..Default::default()
+        };
+        let gc = ReverseGeocoder::new(vec![
+            city(1, 49.35, 8.14, 53000),
+            city(2, 54.11, 10.81, 15000),
+        ]);
+
+        assert_eq!(gc.autocomplete("neu", 1, None, None)[0].id, 1);
+        assert_eq!(gc.autocomplete("neu", 1, None, Some((54.0, 10.8)))[0].id, 2);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::compact::is_allowed;
+use crate::{City, Schema};
+
+/// Restricts which rows of a data file are loaded, see [`ReverseGeocoder::from_file_with_options`].
+///
+/// The options are applied while the file is read, so large exports like `allCountries.txt`
+/// can be loaded without preprocessing them. Empty lists and `None` values don't restrict the
+/// loaded rows.
+///
+/// # Examples
+/// ```rust
+/// let options = geocoder::LoadOptions {
+///     feature_classes: vec![String::from("P")],
+///     country_codes: vec![String::from("DE"), String::from("NL")],
+///     min_population: Some(1000),
+///     drop_alternatenames: true,
+///     ..Default::default()
+/// };
+/// ```
+///
+/// [`ReverseGeocoder::from_file_with_options`]: crate::ReverseGeocoder::from_file_with_options
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadOptions {
+    /// Allowed feature classes, e.g. "P" for populated places
+    pub feature_classes: Vec<String>,
+    /// Allowed feature codes, e.g. "PPLA" or "PPLC"
+    pub feature_codes: Vec<String>,
+    /// Allowed ISO-3166 2-letter country codes
+    pub country_codes: Vec<String>,
+    /// Minimum population, rows without population are dropped
+    pub min_population: Option<u32>,
+    /// Don't keep `alternatenames` in memory. They are often the largest column, but are
+    /// needed to search by alternate names.
+    pub drop_alternatenames: bool,
+    /// Don't keep the digital elevation model, `dem` is empty for all loaded cities.
+    pub drop_dem: bool,
+    /// Layout of the data file if it isn't a GeoNames export
+    pub schema: Option<Schema>,
+    /// Skip rows that can't be parsed instead of failing, see
+    /// [`ReverseGeocoder::from_file_lenient`](crate::ReverseGeocoder::from_file_lenient)
+    pub skip_invalid_rows: bool,
+}
+
+impl LoadOptions {
+    /// Returns `true` if the city should be loaded, and drops the columns that shouldn't be kept.
+    pub(crate) fn apply(&self, city: &mut City) -> bool {
+        if let Some(min_population) = self.min_population {
+            if city.population.unwrap_or(0) < min_population {
+                return false;
+            }
+        }
+        let keep = is_allowed(&self.feature_classes, &city.feature_class)
+            && is_allowed(&self.feature_codes, &city.feature_code)
+            && is_allowed(&self.country_codes, &city.country_code);
+        if keep && self.drop_alternatenames {
+            city.names.drop_alternatenames();
+        }
+        if keep && self.drop_dem {
+            city.dem = Box::default();
+        }
+        keep
+    }
+
+    /// Returns `true` if any option restricts the loaded rows, as opposed to dropping columns.
+    pub(crate) fn restricts_rows(&self) -> bool {
+        !self.feature_classes.is_empty()
+            || !self.feature_codes.is_empty()
+            || !self.country_codes.is_empty()
+            || self.min_population.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ReverseGeocoder;
+    use tracing_test::traced_test;
+
+    #[test]
+    #[traced_test]
+    fn filters_rows_while_loading() {
+        let load = |options: LoadOptions| {
+            ReverseGeocoder::from_file_with_options("../cities.txt", &options).unwrap()
+        };
+
+        let (gc, report) = load(LoadOptions::default());
+        assert_eq!(format!("{}", gc), "ReverseGeocoder<cities=11, tree=11>");
+        assert_eq!(report.rows_filtered, 0);
+
+        let (gc, report) = load(LoadOptions {
+            country_codes: vec![String::from("JP"), String::from("DE")],
+            ..Default::default()
+        });
+        assert_eq!(format!("{}", gc), "ReverseGeocoder<cities=3, tree=3>");
+        assert_eq!(report.rows_read, 11);
+        assert_eq!(report.rows_filtered, 8);
+
+        let (gc, _) = load(LoadOptions {
+            min_population: Some(10_000_000),
+            feature_codes: vec![String::from("PPLC")],
+            ..Default::default()
+        });
+        // Beijing, Dhaka and Mexico City
+        assert_eq!(format!("{}", gc), "ReverseGeocoder<cities=3, tree=3>");
+        let (_, city) = gc.search(35.69, 139.69, 1)[0];
+        assert_eq!(city.name(), "Beijing");
+
+        let (gc, _) = load(LoadOptions {
+            drop_alternatenames: true,
+            drop_dem: true,
+            ..Default::default()
+        });
+        assert!(gc.find("PEKING", 1).is_empty());
+        assert_eq!(gc.find("Beijing", 1)[0].alternatenames(), "");
+        assert!(gc.find("Beijing", 1)[0].dem.is_empty());
+    }
+}
@@ -0,0 +1,189 @@
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::compact::is_allowed;
+use crate::{
+    errors, parse_csv_file, to_f64, Code, Float, LoadOptions, LoadReport, Locatable,
+    ReverseGeocoder,
+};
+
+/// Column names of the GeoNames postal code export, used in error messages.
+const POSTAL_CODE_COLUMNS: [&str; 12] = [
+    "country code",
+    "postal code",
+    "place name",
+    "admin name1",
+    "admin code1",
+    "admin name2",
+    "admin code2",
+    "admin name3",
+    "admin code3",
+    "latitude",
+    "longitude",
+    "accuracy",
+];
+
+/// Row of the http://www.geonames.org postal code export, e.g. `allCountries.txt` of
+/// https://download.geonames.org/export/zip/allCountries.zip.
+///
+/// Only the country code is interned, admin names and codes take too many distinct values.
+///
+/// # Example
+/// ```rust
+/// let postal_code = geocoder::PostalCode {
+///     country_code: "DE".into(),
+///     postal_code: String::from("50667"),
+///     place_name: String::from("Köln"),
+///     admin_name1: String::from("Nordrhein-Westfalen"),
+///     admin_code1: String::from("NW"),
+///     latitude: 50.9387,
+///     longitude: 6.9547,
+///     accuracy: Some(4),
+///     ..Default::default()
+/// };
+/// assert_eq!(postal_code.to_string(), "50667 Köln, DE");
+/// ```
+#[rustfmt::skip]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct PostalCode {
+    pub country_code: Code,      // iso country code, 2 characters
+    pub postal_code: String,     // varchar(20)
+    pub place_name: String,      // varchar(180)
+    pub admin_name1: String,     // 1. order subdivision (state) varchar(100)
+    pub admin_code1: String,     // 1. order subdivision (state) varchar(20)
+    pub admin_name2: String,     // 2. order subdivision (county/province) varchar(100)
+    pub admin_code2: String,     // 2. order subdivision (county/province) varchar(20)
+    pub admin_name3: String,     // 3. order subdivision (community) varchar(100)
+    pub admin_code3: String,     // 3. order subdivision (community) varchar(20)
+    pub latitude: Float,         // estimated latitude (wgs84)
+    pub longitude: Float,        // estimated longitude (wgs84)
+    pub accuracy: Option<u8>,    // accuracy of lat/lng from 1=estimated, 4=geonameid, 6=centroid of addresses or shape
+}
+
+impl PostalCode {
+    /// Latitude and longitude as f64, without the noise of widening an f32, see
+    /// [`City::lat_lng`](crate::City::lat_lng).
+    pub fn lat_lng(&self) -> (f64, f64) {
+        (to_f64(self.latitude), to_f64(self.longitude))
+    }
+}
+
+impl Locatable for PostalCode {
+    fn coordinates(&self) -> (Float, Float) {
+        (self.latitude, self.longitude)
+    }
+
+    fn country_code(&self) -> &str {
+        &self.country_code
+    }
+}
+
+impl Display for PostalCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}, {}",
+            self.postal_code, self.place_name, self.country_code
+        )
+    }
+}
+
+impl ReverseGeocoder<PostalCode> {
+    /// Initialize a postal code index from a GeoNames postal code export.
+    ///
+    /// The file may be a single country or `allCountries`, also as downloaded zip archive. Of
+    /// the `options`, only [`LoadOptions::country_codes`] and
+    /// [`LoadOptions::skip_invalid_rows`] apply.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use geocoder::{LoadOptions, PostalCode, ReverseGeocoder};
+    ///
+    /// let (gc, _) =
+    ///     ReverseGeocoder::<PostalCode>::from_postal_codes_file("allCountries.zip", &LoadOptions::default())?;
+    /// let (_, postal_code) = gc.search(50.94, 6.96, 1)[0];
+    /// println!("{}", postal_code.postal_code);
+    /// # Ok::<(), geocoder::errors::Error>(())
+    /// ```
+    pub fn from_postal_codes_file(
+        filename: &str,
+        options: &LoadOptions,
+    ) -> errors::Result<(Self, LoadReport)> {
+        let (postal_codes, report) = parse_csv_file(
+            filename,
+            &POSTAL_CODE_COLUMNS,
+            options.skip_invalid_rows,
+            |postal_code: &mut PostalCode| {
+                is_allowed(&options.country_codes, &postal_code.country_code)
+            },
+        )?;
+        for row in &report.skipped {
+            tracing::warn!("Skipping invalid row in {}: {}", filename, row);
+        }
+        tracing::info!("Loaded postal codes from {}: {}", filename, report);
+        Ok((Self::new(postal_codes), report))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::write_test_file;
+    use tracing_test::traced_test;
+
+    //noinspection SpellCheckingInspection
+    fn postal_codes() -> String {
+        write_test_file(
+            "postal_codes.txt",
+            "DE\t50667\tKöln\tNordrhein-Westfalen\tNW\tRegierungsbezirk Köln\t053\tKöln, Stadt\t05315\t50.9387\t6.9547\t4\n\
+             DE\t10115\tBerlin\tBerlin\tBE\t\t00\tBerlin, Stadt\t11000\t52.5323\t13.3846\t4\n\
+             NL\t1011\tAmsterdam\tNoord-Holland\t07\tAmsterdam\t0363\t\t\t52.3738\t4.9041\t\n",
+        )
+    }
+
+    #[test]
+    #[traced_test]
+    fn finds_nearest_postal_code() {
+        let (gc, report) =
+            ReverseGeocoder::from_postal_codes_file(&postal_codes(), &LoadOptions::default())
+                .unwrap();
+        assert_eq!(report.rows_read, 3);
+
+        let (distance, postal_code) = gc.search(50.94, 6.96, 1)[0];
+        assert_eq!(postal_code.to_string(), "50667 Köln, DE");
+        assert_eq!(postal_code.admin_name1, "Nordrhein-Westfalen");
+        assert_eq!(postal_code.admin_name3, "Köln, Stadt");
+        assert_eq!(postal_code.accuracy, Some(4));
+        assert!(distance < 1000.0);
+
+        let (_, postal_code) = gc.search(52.37, 4.9, 1)[0];
+        assert_eq!(postal_code.postal_code, "1011");
+        assert_eq!(postal_code.accuracy, None);
+    }
+
+    #[test]
+    #[traced_test]
+    fn filters_postal_codes_by_country() {
+        let options = LoadOptions {
+            country_codes: vec![String::from("NL")],
+            ..Default::default()
+        };
+        let (gc, report) =
+            ReverseGeocoder::from_postal_codes_file(&postal_codes(), &options).unwrap();
+        assert_eq!(report.rows_filtered, 2);
+        assert_eq!(gc.search(50.94, 6.96, 1)[0].1.place_name, "Amsterdam");
+    }
+
+    #[test]
+    #[traced_test]
+    fn rejects_invalid_postal_codes() {
+        let file = write_test_file(
+            "invalid_postal_codes.txt",
+            "DE\t50667\tKöln\t\t\t\t\t\t\tx\t6.9\t\n",
+        );
+        let error =
+            ReverseGeocoder::from_postal_codes_file(&file, &LoadOptions::default()).unwrap_err();
+        assert!(error.to_string().contains("column 'latitude'"));
+    }
+}
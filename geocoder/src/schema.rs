@@ -0,0 +1,374 @@
+use std::io::Read;
+
+use csv::{ReaderBuilder, StringRecord};
+
+use crate::errors::{Error, RowError};
+use crate::{errors, City, Code, Float, LoadReport, Names, Properties};
+
+/// Fields of [`City`] that columns can be mapped to.
+const FIELDS: [&str; 19] = [
+    "id",
+    "name",
+    "asciiname",
+    "alternatenames",
+    "latitude",
+    "longitude",
+    "feature_class",
+    "feature_code",
+    "country_code",
+    "cc2",
+    "admin1_code",
+    "admin2_code",
+    "admin3_code",
+    "admin4_code",
+    "population",
+    "elevation",
+    "dem",
+    "timezone",
+    "modification_date",
+];
+
+/// Layout of a CSV or TSV file that doesn't follow the GeoNames export, see
+/// [`LoadOptions::schema`](crate::LoadOptions::schema).
+///
+/// Columns are referenced by their name if the file has a header row, otherwise by their
+/// 0-based index. Only `latitude` and `longitude` have to be mapped, other fields are left
+/// empty. Without a mapped `id`, cities are numbered by their row, starting at 1.
+///
+/// # Example
+/// ```rust
+/// let schema = geocoder::Schema {
+///     columns: vec![
+///         (String::from("name"), String::from("Store")),
+///         (String::from("latitude"), String::from("Lat")),
+///         (String::from("longitude"), String::from("Lon")),
+///     ],
+///     passthrough: vec![String::from("Opening hours")],
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schema {
+    /// Column delimiter, `,` by default
+    pub delimiter: u8,
+    /// Whether the first row contains the column names
+    pub has_headers: bool,
+    /// Pairs of [`City`] field names, e.g. `feature_code`, and the columns they are read from
+    pub columns: Vec<(String, String)>,
+    /// Columns copied to [`City::properties`], named after the column
+    pub passthrough: Vec<String>,
+    /// Rows starting with this character are skipped, none by default
+    pub comment: Option<u8>,
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            has_headers: true,
+            columns: vec![],
+            passthrough: vec![],
+            comment: None,
+        }
+    }
+}
+
+impl Schema {
+    /// Read all rows of `data` into cities. Invalid rows are skipped if `lenient` is set, rows for
+    /// which `keep` returns `false` are dropped.
+    pub(crate) fn read<D, K>(
+        &self,
+        data: D,
+        filename: &str,
+        lenient: bool,
+        mut keep: K,
+    ) -> errors::Result<(Vec<City>, LoadReport)>
+    where
+        D: Read,
+        K: FnMut(&mut City) -> bool,
+    {
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .has_headers(self.has_headers)
+            .comment(self.comment)
+            .from_reader(data);
+        let headers: Vec<String> = match self.has_headers {
+            true => reader.headers()?.iter().map(String::from).collect(),
+            false => vec![],
+        };
+
+        let mut fields = vec![];
+        for (field, column) in &self.columns {
+            match FIELDS.iter().find(|f| *f == field) {
+                Some(field) => fields.push((*field, self.index(&headers, column)?)),
+                None => return Err(Error::InvalidSchema(format!("unknown field '{}'", field))),
+            }
+        }
+        for required in ["latitude", "longitude"] {
+            if !fields.iter().any(|(field, _)| *field == required) {
+                return Err(Error::InvalidSchema(format!(
+                    "no column for '{}'",
+                    required
+                )));
+            }
+        }
+        let passthrough = self
+            .passthrough
+            .iter()
+            .map(|column| Ok((column.as_str(), self.index(&headers, column)?)))
+            .collect::<errors::Result<Vec<_>>>()?;
+        let columns: Vec<&str> = headers.iter().map(String::as_str).collect();
+
+        let mut records = vec![];
+        let mut report = LoadReport::default();
+        for result in reader.records() {
+            report.rows_read += 1;
+            let row = match result {
+                Ok(row) => row,
+                Err(e) => match RowError::from_csv(&e, &columns) {
+                    Some(row) => {
+                        report.skip(row, filename, lenient)?;
+                        continue;
+                    }
+                    None => return Err(e.into()),
+                },
+            };
+            match to_city(&row, &fields, &passthrough, report.rows_read as u32) {
+                Ok(mut city) => {
+                    if keep(&mut city) {
+                        records.push(city);
+                    } else {
+                        report.rows_filtered += 1;
+                    }
+                }
+                Err((idx, reason)) => {
+                    let row = RowError {
+                        line: row.position().map(|pos| pos.line()).unwrap_or(0),
+                        column: Some(column_name(&columns, idx)),
+                        reason,
+                    };
+                    report.skip(row, filename, lenient)?;
+                }
+            }
+        }
+        Ok((records, report))
+    }
+
+    /// Index of a column given by name or, without a header row, by index.
+    fn index(&self, headers: &[String], column: &str) -> errors::Result<usize> {
+        let idx = match self.has_headers {
+            true => headers.iter().position(|header| header == column),
+            false => column.parse().ok(),
+        };
+        idx.ok_or_else(|| Error::InvalidSchema(format!("column '{}' not found", column)))
+    }
+}
+
+/// Build a city from the mapped columns of a row. Fails with the index of the offending column.
+fn to_city(
+    row: &StringRecord,
+    fields: &[(&str, usize)],
+    passthrough: &[(&str, usize)],
+    row_number: u32,
+) -> Result<City, (usize, String)> {
+    let mut city = City {
+        id: row_number,
+        ..Default::default()
+    };
+    let (mut name, mut asciiname, mut alternatenames) = ("", "", "");
+    for (field, idx) in fields {
+        let value = row.get(*idx).unwrap_or("");
+        let invalid = |e: &dyn std::fmt::Display| (*idx, e.to_string());
+        match *field {
+            "id" => city.id = value.parse().map_err(|e| invalid(&e))?,
+            "name" => name = value,
+            "asciiname" => asciiname = value,
+            "alternatenames" => alternatenames = value,
+            "latitude" => city.latitude = value.parse::<Float>().map_err(|e| invalid(&e))?,
+            "longitude" => city.longitude = value.parse::<Float>().map_err(|e| invalid(&e))?,
+            "population" => city.population = optional(value).map_err(|e| invalid(&e))?,
+            "elevation" => city.elevation = optional(value).map_err(|e| invalid(&e))?,
+            "admin3_code" => city.admin3_code = value.into(),
+            "admin4_code" => city.admin4_code = value.into(),
+            "dem" => city.dem = value.into(),
+            "modification_date" => city.modification_date = value.into(),
+            code => *code_field(&mut city, code) = Code::from(value),
+        }
+    }
+    city.names = Names::new(name, asciiname, alternatenames);
+    city.properties = passthrough
+        .iter()
+        .map(|(key, idx)| (*key, row.get(*idx).unwrap_or("")))
+        .collect::<Properties>();
+    Ok(city)
+}
+
+/// The code field of a city named `field`.
+fn code_field<'a>(city: &'a mut City, field: &str) -> &'a mut Code {
+    match field {
+        "feature_class" => &mut city.feature_class,
+        "feature_code" => &mut city.feature_code,
+        "country_code" => &mut city.country_code,
+        "cc2" => &mut city.cc2,
+        "admin1_code" => &mut city.admin1_code,
+        "admin2_code" => &mut city.admin2_code,
+        "timezone" => &mut city.timezone,
+        _ => unreachable!("not a code field: {}", field),
+    }
+}
+
+/// Parse an optional number, empty values are `None`.
+fn optional<T: std::str::FromStr>(value: &str) -> Result<Option<T>, T::Err> {
+    match value.trim() {
+        "" => Ok(None),
+        value => value.parse().map(Some),
+    }
+}
+
+fn column_name(columns: &[&str], idx: usize) -> String {
+    columns
+        .get(idx)
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| format!("#{}", idx + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::write_test_file;
+    use crate::{LoadOptions, ReverseGeocoder};
+    use tracing_test::traced_test;
+
+    fn stores() -> String {
+        write_test_file(
+            "stores.csv",
+            "Store;Lon;Lat;Country;Opening hours\n\
+             Cologne;6.96;50.94;DE;8-20\n\
+             Berlin;13.40;52.52;DE;\n\
+             Amsterdam;4.90;52.37;NL;9-18\n",
+        )
+    }
+
+    fn store_schema() -> Schema {
+        let columns = [
+            ("name", "Store"),
+            ("latitude", "Lat"),
+            ("longitude", "Lon"),
+            ("country_code", "Country"),
+        ];
+        Schema {
+            delimiter: b';',
+            columns: columns
+                .iter()
+                .map(|(field, column)| (field.to_string(), column.to_string()))
+                .collect(),
+            passthrough: vec![String::from("Opening hours")],
+            ..Default::default()
+        }
+    }
+
+    fn load(schema: Schema) -> errors::Result<(ReverseGeocoder, LoadReport)> {
+        let options = LoadOptions {
+            schema: Some(schema),
+            country_codes: vec![String::from("DE")],
+            ..Default::default()
+        };
+        ReverseGeocoder::from_file_with_options(&stores(), &options)
+    }
+
+    #[test]
+    #[traced_test]
+    fn reads_mapped_and_passthrough_columns() {
+        let (gc, report) = load(store_schema()).unwrap();
+        assert_eq!(report.rows_read, 3);
+        assert_eq!(report.rows_filtered, 1);
+
+        let (_, city) = gc.search(51.08, 6.31, 1)[0];
+        assert_eq!(format!("{}", city), "Cologne, DE");
+        assert_eq!(city.id, 1);
+        assert_eq!(city.lat_lng(), (50.94, 6.96));
+        assert_eq!(city.properties.get("Opening hours"), Some("8-20"));
+        assert_eq!(
+            gc.find("berlin", 1)[0].properties.get("Opening hours"),
+            Some("")
+        );
+    }
+
+    #[test]
+    #[traced_test]
+    fn skips_comments_only_if_configured() {
+        let file = write_test_file(
+            "hashes.csv",
+            "Lat,Lon,Store\n50.94,6.96,#1 Store\n# 52.52,13.40,Closed\n",
+        );
+        let mut schema = Schema {
+            columns: vec![
+                (String::from("latitude"), String::from("Lat")),
+                (String::from("longitude"), String::from("Lon")),
+            ],
+            passthrough: vec![String::from("Store")],
+            ..Default::default()
+        };
+        let options = |schema: &Schema| LoadOptions {
+            schema: Some(schema.clone()),
+            skip_invalid_rows: true,
+            ..Default::default()
+        };
+
+        let (gc, report) =
+            ReverseGeocoder::from_file_with_options(&file, &options(&schema)).unwrap();
+        assert_eq!(gc.cities().len(), 1);
+        assert_eq!(report.rows_skipped(), 1);
+
+        schema.comment = Some(b'#');
+        let (gc, report) =
+            ReverseGeocoder::from_file_with_options(&file, &options(&schema)).unwrap();
+        assert_eq!(gc.cities()[0].properties.get("Store"), Some("#1 Store"));
+        assert_eq!(report.rows_read, 1);
+    }
+
+    #[test]
+    #[traced_test]
+    fn reads_columns_by_index_without_header() {
+        let file = write_test_file("stores.tsv", "7\t50.94\t6.96\tCologne\n");
+        let schema = Schema {
+            delimiter: b'\t',
+            has_headers: false,
+            columns: vec![
+                (String::from("id"), String::from("0")),
+                (String::from("latitude"), String::from("1")),
+                (String::from("longitude"), String::from("2")),
+                (String::from("name"), String::from("3")),
+            ],
+            ..Default::default()
+        };
+        let options = LoadOptions {
+            schema: Some(schema),
+            ..Default::default()
+        };
+        let (gc, _) = ReverseGeocoder::from_file_with_options(&file, &options).unwrap();
+        assert_eq!(gc.find("cologne", 1)[0].id, 7);
+    }
+
+    #[test]
+    #[traced_test]
+    fn rejects_invalid_schemas_and_rows() {
+        let error = |schema| load(schema).unwrap_err().to_string();
+
+        let mut schema = store_schema();
+        schema.columns[0].0 = String::from("title");
+        assert_eq!(error(schema), "invalid schema: unknown field 'title'");
+
+        let mut schema = store_schema();
+        schema.passthrough.push(String::from("Phone"));
+        assert_eq!(error(schema), "invalid schema: column 'Phone' not found");
+
+        let mut schema = store_schema();
+        schema.columns.remove(1);
+        assert_eq!(error(schema), "invalid schema: no column for 'latitude'");
+
+        let mut schema = store_schema();
+        schema.columns[3].0 = String::from("population");
+        assert!(error(schema).ends_with("line 2, column 'Country': invalid digit found in string"));
+    }
+}
@@ -0,0 +1,344 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+use bincode::Options;
+use kiddo::float::kdtree::KdTree;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::admin::AdminDivisions;
+use crate::country::Countries;
+use crate::errors::Error;
+use crate::localized::LocalizedNames;
+use crate::names::NameIndex;
+use crate::{errors, id_index, Float, Locatable, ReverseGeocoder};
+
+/// Identifies a file as a geocoder snapshot.
+const MAGIC: &[u8; 8] = b"GEOCSNAP";
+
+/// Version of the snapshot format, increased on every incompatible change.
+const VERSION: u32 = 5;
+
+/// Size of the header in bytes: magic, version, float size, checksum and payload length.
+const HEADER_LEN: usize = 8 + 4 + 1 + 4 + 8;
+
+/// Offset of the checksum in the header, which is written after the payload.
+const CHECKSUM_OFFSET: u64 = 8 + 4 + 1;
+
+/// Size in bytes of the float type the snapshot was written with, see [`Float`].
+const FLOAT_SIZE: u8 = std::mem::size_of::<Float>() as u8;
+
+type Payload<T> = (
+    Vec<T>,
+    KdTree<Float, usize, 3, 32, u16>,
+    NameIndex,
+    AdminDivisions,
+    AdminDivisions,
+    Countries,
+    LocalizedNames,
+);
+
+/// Returns `true` if the file starts with the snapshot header, `false` for any other file
+/// (e.g. a GeoNames TSV).
+///
+/// # Example
+/// ```rust
+/// assert!(!geocoder::is_snapshot("../cities.txt")?);
+/// # Ok::<(), geocoder::errors::Error>(())
+/// ```
+pub fn is_snapshot(filename: &str) -> errors::Result<bool> {
+    let mut magic = [0u8; 8];
+    let mut file = File::open(filename)?;
+    match file.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == MAGIC),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+impl<T: Locatable + Serialize + DeserializeOwned> ReverseGeocoder<T> {
+    /// Write the cities, the prebuilt tree, the name index and the loaded lookup tables, including
+    /// localized names, to a binary snapshot, which loads a lot faster than the original data
+    /// files.
+    ///
+    /// The snapshot starts with a versioned header and a checksum of its contents. Snapshots
+    /// written with the `f64` feature can only be loaded with the `f64` feature and vice versa.
+    /// Own records can be saved as well if they are serializable, and must be loaded as the same
+    /// type.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// let gc = geocoder::ReverseGeocoder::from_file("cities500.txt");
+    /// gc.save_snapshot("cities500.snapshot")?;
+    /// # Ok::<(), geocoder::errors::Error>(())
+    /// ```
+    pub fn save_snapshot(&self, filename: &str) -> errors::Result<()> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&[FLOAT_SIZE])?;
+        // Checksum and length aren't known yet, they are filled in once the payload is written
+        writer.write_all(&[0; HEADER_LEN - CHECKSUM_OFFSET as usize])?;
+
+        let mut payload = Checksummed::new(&mut writer);
+        bincode::serialize_into(
+            &mut payload,
+            &(
+                &self.cities,
+                &self.tree,
+                &self.names,
+                &self.admin1,
+                &self.admin2,
+                &self.countries,
+                &self.localized,
+            ),
+        )
+        .map_err(|e| invalid_snapshot(filename, e.to_string()))?;
+        let (checksum, len) = payload.finish();
+
+        writer.seek(SeekFrom::Start(CHECKSUM_OFFSET))?;
+        writer.write_all(&checksum.to_le_bytes())?;
+        writer.write_all(&len.to_le_bytes())?;
+        writer.flush()?;
+        tracing::info!(
+            "Saved snapshot of {} cities to {}",
+            self.cities.len(),
+            filename
+        );
+        Ok(())
+    }
+
+    /// Initialize ReverseGeocoder from a snapshot written by [`ReverseGeocoder::save_snapshot`].
+    ///
+    /// Fails if the file isn't a snapshot, was written by an incompatible version or its
+    /// checksum doesn't match. The type of the records can't be inferred from the file, so it
+    /// has to be given.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use geocoder::{City, ReverseGeocoder};
+    ///
+    /// let gc = ReverseGeocoder::<City>::load_snapshot("cities500.snapshot")?;
+    /// # Ok::<(), geocoder::errors::Error>(())
+    /// ```
+    pub fn load_snapshot(filename: &str) -> errors::Result<ReverseGeocoder<T>> {
+        tracing::debug!("Loading snapshot {}", filename);
+        let mut reader = BufReader::new(File::open(filename)?);
+        let (checksum, len) = read_header(filename, &mut reader)?;
+
+        let mut payload = Checksummed::new((&mut reader).take(len));
+        // The limit rejects corrupt lengths before anything is allocated for them
+        let result: bincode::Result<Payload<T>> = bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .with_limit(len)
+            .deserialize_from(&mut payload);
+        // The checksum covers the whole payload, even if it couldn't be deserialized
+        std::io::copy(&mut payload, &mut std::io::sink())?;
+        let (actual_checksum, read) = payload.finish();
+        let found = read + std::io::copy(&mut reader, &mut std::io::sink())?;
+        if found != len {
+            return Err(invalid_snapshot(
+                filename,
+                format!("expected {} bytes of data, found {}", len, found),
+            ));
+        }
+        if actual_checksum != checksum {
+            return Err(invalid_snapshot(filename, "checksum mismatch"));
+        }
+        let (cities, tree, names, admin1, admin2, countries, localized) =
+            result.map_err(|e| invalid_snapshot(filename, e.to_string()))?;
+        tracing::info!("Loaded snapshot of {} cities", cities.len());
+
+        Ok(Self {
+            ids: id_index(&cities),
+            cities,
+            tree,
+            names,
+            admin1,
+            admin2,
+            countries,
+            localized,
+            ..Default::default()
+        })
+    }
+}
+
+/// Validate the header and return the checksum and length of the payload following it.
+fn read_header(filename: &str, reader: &mut impl Read) -> errors::Result<(u32, u64)> {
+    let mut bytes = [0u8; HEADER_LEN];
+    match reader.read_exact(&mut bytes) {
+        Ok(()) if &bytes[0..8] == MAGIC => {}
+        Ok(()) => return Err(invalid_snapshot(filename, "not a snapshot")),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+            return Err(invalid_snapshot(filename, "not a snapshot"))
+        }
+        Err(e) => return Err(e.into()),
+    }
+    let version = u32::from_le_bytes(bytes[8..12].try_into().unwrap());
+    if version != VERSION {
+        return Err(invalid_snapshot(
+            filename,
+            format!("unsupported version {}, expected {}", version, VERSION),
+        ));
+    }
+    let float_size = bytes[12];
+    if float_size != FLOAT_SIZE {
+        return Err(invalid_snapshot(
+            filename,
+            format!(
+                "written with {}-bit coordinates, expected {}-bit",
+                float_size * 8,
+                FLOAT_SIZE * 8
+            ),
+        ));
+    }
+    let checksum = u32::from_le_bytes(bytes[13..17].try_into().unwrap());
+    let len = u64::from_le_bytes(bytes[17..25].try_into().unwrap());
+    Ok((checksum, len))
+}
+
+/// Reader or writer that computes the CRC32 checksum and length of the data passing through.
+struct Checksummed<I> {
+    inner: I,
+    hasher: crc32fast::Hasher,
+    len: u64,
+}
+
+impl<I> Checksummed<I> {
+    fn new(inner: I) -> Self {
+        Self {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+            len: 0,
+        }
+    }
+
+    /// Checksum and length of the data read or written so far.
+    fn finish(self) -> (u32, u64) {
+        (self.hasher.finalize(), self.len)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.hasher.update(bytes);
+        self.len += bytes.len() as u64;
+    }
+}
+
+impl<R: Read> Read for Checksummed<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Checksummed<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub(crate) fn invalid_snapshot(filename: &str, reason: impl Into<String>) -> Error {
+    Error::InvalidSnapshot {
+        file: filename.to_string(),
+        reason: reason.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::write_test_file;
+    use crate::{City, PostalCode};
+    use tracing_test::traced_test;
+
+    fn snapshot_file(name: &str) -> String {
+        let path = write_test_file(name, "");
+        let mut gc = ReverseGeocoder::from_file("../cities.txt");
+        let admin1 = write_test_file(
+            &format!("{}-admin1.txt", name),
+            "DE.07\tNorth Rhine-Westphalia\tNorth Rhine-Westphalia\t2861876\n",
+        );
+        gc.load_admin1_codes(&admin1).unwrap();
+        gc.save_snapshot(&path).unwrap();
+        path
+    }
+
+    #[test]
+    #[traced_test]
+    fn restores_geocoder_from_snapshot() {
+        let path = snapshot_file("roundtrip.snapshot");
+        assert!(is_snapshot(&path).unwrap());
+        assert!(!is_snapshot("../cities.txt").unwrap());
+
+        let gc = ReverseGeocoder::<City>::load_snapshot(&path).unwrap();
+        assert_eq!(format!("{}", gc), "ReverseGeocoder<cities=11, tree=11>");
+        let (_, city) = gc.search(51.08, 6.31, 1)[0];
+        assert_eq!(city.id, 2929622);
+        assert_eq!(gc.admin1(city).unwrap().name, "North Rhine-Westphalia");
+        assert_eq!(gc.find("PEKING", 1)[0].name(), "Beijing");
+        assert_eq!(gc.get(2929622).unwrap().name(), "Erkelenz");
+    }
+
+    #[test]
+    #[traced_test]
+    fn restores_own_records_from_snapshot() {
+        let path = write_test_file("postal.snapshot", "");
+        let postal_code = |postal_code: &str, lat| PostalCode {
+            country_code: "DE".into(),
+            postal_code: postal_code.to_string(),
+            latitude: lat,
+            longitude: 6.96,
+            ..Default::default()
+        };
+        ReverseGeocoder::new(vec![
+            postal_code("50667", 50.94),
+            postal_code("53111", 50.73),
+        ])
+        .save_snapshot(&path)
+        .unwrap();
+
+        let gc = ReverseGeocoder::<PostalCode>::load_snapshot(&path).unwrap();
+        let (_, found) = gc.search(50.7, 7.0, 1)[0];
+        assert_eq!(found, &postal_code("53111", 50.73));
+    }
+
+    #[test]
+    #[traced_test]
+    fn rejects_invalid_snapshots() {
+        let reason = |path: &str| match ReverseGeocoder::<City>::load_snapshot(path) {
+            Err(Error::InvalidSnapshot { reason, .. }) => reason,
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("expected an error"),
+        };
+        assert_eq!(reason("../cities.txt"), "not a snapshot");
+
+        let path = snapshot_file("corrupt.snapshot");
+        let mut bytes = std::fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(reason(&path), "checksum mismatch");
+
+        // Corrupt data is reported as such, even if it can't be deserialized
+        bytes[last] ^= 0xff;
+        bytes[HEADER_LEN + 7] = 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(reason(&path), "checksum mismatch");
+
+        bytes[8] = 99;
+        std::fs::write(&path, &bytes).unwrap();
+        assert_eq!(reason(&path), "unsupported version 99, expected 5");
+
+        bytes[8] = VERSION as u8;
+        std::fs::write(&path, &bytes[..HEADER_LEN + 10]).unwrap();
+        assert!(reason(&path).starts_with("expected"));
+    }
+}
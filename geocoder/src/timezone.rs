@@ -0,0 +1,149 @@
+use chrono::{DateTime, FixedOffset, Offset, TimeZone, Utc};
+use chrono_tz::{OffsetComponents, Tz};
+
+use crate::{errors, Boundaries, Float, Locatable, ReverseGeocoder};
+
+/// Feature property of the timezone id in the files of
+/// https://github.com/evansiroky/timezone-boundary-builder.
+const TZID_PROPERTY: [&str; 1] = ["tzid"];
+
+/// Offset and local time of a timezone at an instant.
+///
+/// # Example
+/// ```rust
+/// use chrono::{TimeZone, Utc};
+///
+/// let summer = Utc.with_ymd_and_hms(2023, 7, 1, 12, 0, 0).unwrap();
+/// let berlin = geocoder::LocalTime::at("Europe/Berlin", summer).unwrap();
+/// assert_eq!(berlin.utc_offset, 7200);
+/// assert!(berlin.dst);
+/// assert_eq!(berlin.time.to_rfc3339(), "2023-07-01T14:00:00+02:00");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalTime {
+    /// IANA timezone id, e.g. `Europe/Berlin`
+    pub timezone: &'static str,
+    /// Offset from UTC in seconds, including daylight saving time
+    pub utc_offset: i32,
+    /// Whether daylight saving time is in effect
+    pub dst: bool,
+    /// Local time with its offset
+    pub time: DateTime<FixedOffset>,
+}
+
+impl LocalTime {
+    /// Local time in `timezone` at the given instant. Returns `None` for unknown timezones.
+    pub fn at(timezone: &str, time: DateTime<Utc>) -> Option<LocalTime> {
+        let tz: Tz = timezone.parse().ok()?;
+        let local = time.with_timezone(&tz);
+        let offset = local.offset();
+        Some(LocalTime {
+            timezone: tz.name(),
+            utc_offset: offset.fix().local_minus_utc(),
+            dst: offset.dst_offset().num_seconds() != 0,
+            time: local.with_timezone(&offset.fix()),
+        })
+    }
+
+    /// Local time in `timezone` at a Unix timestamp in seconds.
+    pub fn at_timestamp(timezone: &str, timestamp: i64) -> Option<LocalTime> {
+        Self::at(timezone, Utc.timestamp_opt(timestamp, 0).single()?)
+    }
+
+    /// Current local time in `timezone`.
+    pub fn now(timezone: &str) -> Option<LocalTime> {
+        Self::at(timezone, Utc::now())
+    }
+}
+
+impl<T: Locatable> ReverseGeocoder<T> {
+    /// Load timezone boundaries from a GeoJSON file with a `tzid` property per feature, e.g.
+    /// `combined.json` of https://github.com/evansiroky/timezone-boundary-builder, see
+    /// [`Boundaries`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// let mut gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// gc.load_timezone_boundaries("timezones-with-oceans.geojson.zip")?;
+    /// # Ok::<(), geocoder::errors::Error>(())
+    /// ```
+    pub fn load_timezone_boundaries(&mut self, filename: &str) -> errors::Result<()> {
+        self.timezone_boundaries =
+            Boundaries::from_file_with_properties(filename, &TZID_PROPERTY, &TZID_PROPERTY)?;
+        Ok(())
+    }
+
+    /// Loaded timezone boundaries, empty by default.
+    pub fn timezone_boundaries(&self) -> &Boundaries {
+        &self.timezone_boundaries
+    }
+}
+
+impl ReverseGeocoder {
+    /// IANA timezone id at the given coordinates.
+    ///
+    /// Uses the timezone boundaries if they are loaded and contain the point, otherwise the
+    /// timezone of the nearest city, which may be wrong close to zone borders.
+    ///
+    /// # Example
+    /// ```rust
+    /// let gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// let timezone = gc.timezone(52.52, 13.40).unwrap();
+    /// let now = geocoder::LocalTime::now(timezone);
+    /// ```
+    pub fn timezone(&self, lat: Float, lng: Float) -> Option<&str> {
+        if let Some(boundary) = self.timezone_boundaries.containing(lat, lng) {
+            return Some(&boundary.code);
+        }
+        let (_, city) = self.search(lat, lng, 1).into_iter().next()?;
+        (!city.timezone.is_empty()).then_some(city.timezone.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::write_test_file;
+    use tracing_test::traced_test;
+
+    #[test]
+    fn computes_offsets_and_dst() {
+        let winter = Utc.with_ymd_and_hms(2023, 1, 15, 12, 0, 0).unwrap();
+        let berlin = LocalTime::at("Europe/Berlin", winter).unwrap();
+        assert_eq!((berlin.utc_offset, berlin.dst), (3600, false));
+        assert_eq!(berlin.time.to_rfc3339(), "2023-01-15T13:00:00+01:00");
+
+        let sydney = LocalTime::at("Australia/Sydney", winter).unwrap();
+        assert_eq!((sydney.utc_offset, sydney.dst), (39600, true));
+
+        let kolkata = LocalTime::at_timestamp("Asia/Kolkata", 0).unwrap();
+        assert_eq!(kolkata.utc_offset, 19800);
+        assert_eq!(kolkata.time.to_rfc3339(), "1970-01-01T05:30:00+05:30");
+
+        assert_eq!(LocalTime::at("Mars/Olympus_Mons", winter), None);
+    }
+
+    #[test]
+    #[traced_test]
+    fn prefers_timezone_boundaries_over_nearest_city() {
+        let mut gc = ReverseGeocoder::from_file("../cities.txt");
+        // Nearest to Erkelenz
+        assert_eq!(gc.timezone(47.1, 11.0), Some("Europe/Berlin"));
+
+        let boundaries = write_test_file(
+            "timezones.geojson",
+            r#"{"type":"FeatureCollection","features":[{"type":"Feature",
+                "properties":{"tzid":"Europe/Vienna"},
+                "geometry":{"type":"Polygon","coordinates":[[[10,47],[12,47],[12,48],[10,48],[10,47]]]}}]}"#,
+        );
+        gc.load_timezone_boundaries(&boundaries).unwrap();
+        assert_eq!(gc.timezone(47.1, 11.0), Some("Europe/Vienna"));
+        assert_eq!(gc.timezone(52.52, 13.40), Some("Europe/Berlin"));
+        assert_eq!(ReverseGeocoder::default().timezone(52.52, 13.40), None);
+
+        let mut postal_codes = ReverseGeocoder::<crate::PostalCode>::new(vec![]);
+        postal_codes.load_timezone_boundaries(&boundaries).unwrap();
+        let zone = postal_codes.timezone_boundaries().containing(47.1, 11.0);
+        assert_eq!(zone.map(|zone| zone.code.as_str()), Some("Europe/Vienna"));
+    }
+}
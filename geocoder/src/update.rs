@@ -0,0 +1,418 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+use serde::Deserialize;
+
+use crate::{errors, parse_csv_file, City, LoadOptions, ReverseGeocoder, GEONAMES_COLUMNS};
+
+/// Column names of the GeoNames `deletes-YYYY-MM-DD.txt` files, used in error messages.
+const DELETES_COLUMNS: [&str; 3] = ["geonameid", "name", "comment"];
+
+/// Row of a GeoNames `deletes-YYYY-MM-DD.txt` file.
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct DeletedRow {
+    geonameid: u32,
+    name: String,
+    #[serde(default)]
+    comment: String,
+}
+
+/// Number of cities changed by [`ReverseGeocoder::apply_changes`] and the diff loaders.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpdateReport {
+    /// Cities with a new id
+    pub inserted: u64,
+    /// Cities that replaced one with the same id
+    pub updated: u64,
+    /// Cities that were removed
+    pub deleted: u64,
+}
+
+impl Display for UpdateReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} inserted, {} updated, {} deleted",
+            self.inserted, self.updated, self.deleted
+        )
+    }
+}
+
+impl ReverseGeocoder {
+    /// Insert a city, or replace the city with the same id.
+    ///
+    /// Returns `true` if a city was replaced. Each call passes over the whole name index once, so
+    /// to change many cities, use [`ReverseGeocoder::apply_changes`].
+    ///
+    /// # Example
+    /// ```rust
+    /// let mut gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+    /// let mut tokyo = gc.find("tokyo", 1)[0].clone();
+    /// tokyo.population = Some(14_000_000);
+    /// assert!(gc.insert(tokyo));
+    /// assert_eq!(gc.find("tokyo", 1)[0].population, Some(14_000_000));
+    /// ```
+    pub fn insert(&mut self, city: City) -> bool {
+        self.apply_changes(vec![city], &[]).updated > 0
+    }
+
+    /// Remove the city with the given id. Returns `false` if there is none.
+    pub fn remove(&mut self, id: u32) -> bool {
+        self.apply_changes(vec![], &[id]).deleted > 0
+    }
+
+    /// Delete the cities with the given ids, then insert `upserts` or replace the cities with
+    /// the same ids.
+    ///
+    /// The tree is updated in place. The name index is updated in a single pass over its
+    /// entries, without sorting it again, so a change takes time linear in the number of names
+    /// instead of rebuilding the geocoder. Unknown ids are ignored, so applying the same changes
+    /// twice has no further effect.
+    pub fn apply_changes(&mut self, upserts: Vec<City>, deletes: &[u32]) -> UpdateReport {
+        let mut report = UpdateReport::default();
+        // Original index of the cities moved by deletions, by their current index
+        let mut origin: HashMap<usize, usize> = HashMap::new();
+        // Original indices whose names are dropped, current indices whose names are indexed
+        let mut removed: HashSet<usize> = HashSet::new();
+        let mut added: HashSet<usize> = HashSet::new();
+
+        let mut deleted: Vec<usize> = deletes
+            .iter()
+            .filter_map(|id| self.ids.get(id))
+            .copied()
+            .collect();
+        deleted.sort_unstable();
+        deleted.dedup();
+        // Descending, so the last city, which takes the place of a deleted one, is never
+        // deleted itself later on
+        for idx in deleted.into_iter().rev() {
+            let last = self.cities.len() - 1;
+            self.tree.remove(&self.cities[idx].as_xyz(), idx);
+            removed.insert(origin.remove(&idx).unwrap_or(idx));
+            if idx != last {
+                let moved = &self.cities[last];
+                self.tree.remove(&moved.as_xyz(), last);
+                self.tree.add(&moved.as_xyz(), idx);
+                self.ids.insert(moved.id, idx);
+                let original = origin.remove(&last).unwrap_or(last);
+                origin.insert(idx, original);
+            }
+            let city = self.cities.swap_remove(idx);
+            self.ids.remove(&city.id);
+            report.deleted += 1;
+        }
+
+        // Cities from here on are new, they didn't have an index before
+        let first_new = self.cities.len();
+        for city in upserts {
+            match self.ids.get(&city.id).copied() {
+                Some(idx) => {
+                    self.tree.remove(&self.cities[idx].as_xyz(), idx);
+                    self.tree.add(&city.as_xyz(), idx);
+                    if idx < first_new {
+                        removed.insert(origin.get(&idx).copied().unwrap_or(idx));
+                    }
+                    added.insert(idx);
+                    self.cities[idx] = city;
+                    report.updated += 1;
+                }
+                None => {
+                    let idx = self.cities.len();
+                    self.tree.add(&city.as_xyz(), idx);
+                    self.ids.insert(city.id, idx);
+                    added.insert(idx);
+                    self.cities.push(city);
+                    report.inserted += 1;
+                }
+            }
+        }
+
+        let moved: HashMap<usize, usize> = origin
+            .into_iter()
+            .map(|(idx, original)| (original, idx))
+            .collect();
+        self.names.update(&self.cities, &removed, &moved, &added);
+        tracing::info!("Applied changes: {}", report);
+        report
+    }
+
+    /// Apply a GeoNames `modifications-YYYY-MM-DD.txt`, see [`Changes::read_modifications`].
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// let options = geocoder::LoadOptions::default();
+    /// let (mut gc, _) = geocoder::ReverseGeocoder::from_file_with_options("allCountries.txt", &options)?;
+    /// gc.apply_modifications("modifications-2023-07-01.txt", &options)?;
+    /// gc.apply_deletes("deletes-2023-07-01.txt")?;
+    /// # Ok::<(), geocoder::errors::Error>(())
+    /// ```
+    pub fn apply_modifications(
+        &mut self,
+        filename: &str,
+        options: &LoadOptions,
+    ) -> errors::Result<UpdateReport> {
+        let changes = Changes::read_modifications(filename, options)?;
+        Ok(self.apply(changes))
+    }
+
+    /// Apply a GeoNames `deletes-YYYY-MM-DD.txt`, see [`Changes::read_deletes`].
+    pub fn apply_deletes(&mut self, filename: &str) -> errors::Result<UpdateReport> {
+        let changes = Changes::read_deletes(filename)?;
+        Ok(self.apply(changes))
+    }
+
+    /// Apply changes read from a diff file. Upserts with unknown ids are dropped unless
+    /// [`Changes::insert_new`] is set.
+    pub fn apply(&mut self, mut changes: Changes) -> UpdateReport {
+        if !changes.insert_new {
+            changes
+                .upserts
+                .retain(|city| self.ids.contains_key(&city.id));
+        }
+        self.apply_changes(changes.upserts, &changes.deletes)
+    }
+}
+
+/// Cities to insert or replace and ids to delete, read from a GeoNames daily diff file.
+///
+/// Reading doesn't need the geocoder, so a shared geocoder only has to be locked for
+/// [`ReverseGeocoder::apply_changes`].
+///
+/// # Example
+/// ```rust,no_run
+/// # let mut gc = geocoder::ReverseGeocoder::from_file("../cities.txt");
+/// let changes = geocoder::Changes::read_deletes("deletes-2023-07-01.txt")?;
+/// gc.apply(changes);
+/// # Ok::<(), geocoder::errors::Error>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Changes {
+    pub upserts: Vec<City>,
+    pub deletes: Vec<u32>,
+    /// Insert upserts with ids that aren't loaded. Otherwise [`ReverseGeocoder::apply`] only
+    /// replaces loaded cities, since diff files cover the whole GeoNames database.
+    pub insert_new: bool,
+}
+
+impl Changes {
+    /// Read a GeoNames `modifications-YYYY-MM-DD.txt`, which lists new and changed rows in the
+    /// format of the data file.
+    ///
+    /// The file lists changes to every place in the GeoNames database, not just the ones in an
+    /// extract like `cities500.txt`. Unless the `options` restrict the rows, only cities which
+    /// are already loaded are updated, set [`Changes::insert_new`] to insert the others as well.
+    /// Rows are filtered by the `options` like the data file, except for
+    /// [`LoadOptions::schema`], and the rows they admit are inserted. Changed cities which no
+    /// longer match the options are deleted.
+    pub fn read_modifications(filename: &str, options: &LoadOptions) -> errors::Result<Changes> {
+        let mut deletes = vec![];
+        let (upserts, report) = parse_csv_file(
+            filename,
+            &GEONAMES_COLUMNS,
+            options.skip_invalid_rows,
+            |city: &mut City| {
+                let keep = options.apply(city);
+                if !keep {
+                    deletes.push(city.id);
+                }
+                keep
+            },
+        )?;
+        for row in &report.skipped {
+            tracing::warn!("Skipping invalid row in {}: {}", filename, row);
+        }
+        Ok(Changes {
+            upserts,
+            deletes,
+            insert_new: options.restricts_rows(),
+        })
+    }
+
+    /// Read a GeoNames `deletes-YYYY-MM-DD.txt`, which lists the ids of deleted rows.
+    pub fn read_deletes(filename: &str) -> errors::Result<Changes> {
+        let (rows, _) =
+            parse_csv_file::<DeletedRow, _>(filename, &DELETES_COLUMNS, false, |_| true)?;
+        Ok(Changes {
+            upserts: vec![],
+            deletes: rows.iter().map(|row| row.geonameid).collect(),
+            insert_new: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::write_test_file;
+    use crate::Names;
+    use tracing_test::traced_test;
+
+    /// Every city is found at its location, by its name and by its id.
+    fn assert_consistent(gc: &ReverseGeocoder) {
+        for city in gc.cities() {
+            assert_eq!(gc.get(city.id).map(|c| c.id), Some(city.id));
+            let (_, found) = gc.search(city.latitude, city.longitude, 1)[0];
+            assert_eq!(found.id, city.id, "{} not found by location", city);
+            let ids: Vec<u32> = gc
+                .find_exact(city.name(), 10)
+                .iter()
+                .map(|c| c.id)
+                .collect();
+            assert!(ids.contains(&city.id), "{} not found by name", city);
+        }
+        assert_eq!(
+            format!("{}", gc),
+            format!("ReverseGeocoder<cities={0}, tree={0}>", gc.cities().len())
+        );
+    }
+
+    #[test]
+    #[traced_test]
+    fn inserts_updates_and_removes_cities() {
+        let mut gc = ReverseGeocoder::from_file("../cities.txt");
+        let erkelenz = gc.find("erkelenz", 1)[0].clone();
+
+        let moved = City {
+            names: Names::new("Erkelenz-Mitte", "Erkelenz-Mitte", ""),
+            latitude: 51.2,
+            ..erkelenz.clone()
+        };
+        assert!(gc.insert(moved));
+        assert!(gc.find_exact("Erkelenz", 1).is_empty());
+        assert_eq!(gc.search(51.2, 6.31, 1)[0].1.name(), "Erkelenz-Mitte");
+
+        let hueckelhoven = City {
+            id: 2897216,
+            names: Names::from("Hückelhoven"),
+            latitude: 51.0554,
+            longitude: 6.22658,
+            ..erkelenz
+        };
+        assert!(!gc.insert(hueckelhoven));
+        assert_eq!(gc.search(51.05, 6.22, 1)[0].1.id, 2897216);
+
+        // Beijing is first, so the last city takes its place
+        assert!(gc.remove(1816670));
+        assert!(!gc.remove(1816670));
+        assert!(gc.find("beijing", 1).is_empty());
+        assert!(gc.get(1816670).is_none());
+        assert_ne!(gc.search(39.9, 116.4, 1)[0].1.id, 1816670);
+        assert_eq!(gc.cities().len(), 11);
+        assert_consistent(&gc);
+    }
+
+    #[test]
+    #[traced_test]
+    fn applies_batches_with_moved_and_new_cities() {
+        let mut gc = ReverseGeocoder::from_file("../cities.txt");
+        let new_city = |id, name: &str, lat| City {
+            id,
+            names: Names::from(name),
+            latitude: lat,
+            longitude: 0.0,
+            ..Default::default()
+        };
+        let mut tokyo = gc.find("tokyo", 1)[0].clone();
+        tokyo.names = Names::from("Tōkyō");
+        let report = gc.apply_changes(
+            vec![
+                new_city(1, "Null Island", 0.0),
+                tokyo,
+                new_city(2, "North", 10.0),
+                new_city(1, "Null Island Twin", 0.0),
+            ],
+            // Cairo, Tokyo (the last city), Shanghai (moved before) and an unknown id
+            &[360630, 1850147, 1796236, 42],
+        );
+        assert_eq!(
+            report,
+            UpdateReport {
+                inserted: 3,
+                updated: 1,
+                deleted: 3
+            }
+        );
+        assert_eq!(gc.find_exact("Null Island Twin", 1)[0].id, 1);
+        assert!(gc.find_exact("Null Island", 1).is_empty());
+        assert_eq!(gc.find_exact("Tōkyō", 1)[0].id, 1850147);
+        assert!(gc.find("cairo", 1).is_empty());
+        assert_consistent(&gc);
+    }
+
+    #[test]
+    #[traced_test]
+    fn applies_diff_files() {
+        let mut gc = ReverseGeocoder::from_file("../cities.txt");
+        let cities = std::fs::read_to_string("../cities.txt").unwrap();
+        let erkelenz = cities
+            .lines()
+            .find(|line| line.contains("Erkelenz"))
+            .unwrap();
+        let dili = cities.lines().find(|line| line.contains("Dili")).unwrap();
+        let modifications = write_test_file(
+            "modifications-2023-07-01.txt",
+            &format!(
+                "{}\n{}\n",
+                erkelenz.replace("44650", "1000000"),
+                dili.replace("150000", "1000")
+            ),
+        );
+        let deletes = write_test_file(
+            "deletes-2023-07-01.txt",
+            "360630\tCairo\tduplicate\n1\tUnknown\t\n",
+        );
+
+        let options = LoadOptions {
+            min_population: Some(10_000),
+            ..Default::default()
+        };
+        let report = gc.apply_modifications(&modifications, &options).unwrap();
+        assert_eq!((report.updated, report.deleted), (1, 1));
+        assert_eq!(gc.find("erkelenz", 1)[0].population, Some(1000000));
+        assert!(gc.find("dili", 1).is_empty());
+
+        assert_eq!(gc.apply_deletes(&deletes).unwrap().deleted, 1);
+        assert_eq!(gc.apply_deletes(&deletes).unwrap().deleted, 0);
+        assert_eq!(gc.cities().len(), 9);
+        assert_consistent(&gc);
+    }
+
+    #[test]
+    #[traced_test]
+    fn only_updates_loaded_cities_by_default() {
+        let mut gc = ReverseGeocoder::from_file("../cities.txt");
+        let cities = std::fs::read_to_string("../cities.txt").unwrap();
+        let erkelenz = cities
+            .lines()
+            .find(|line| line.contains("Erkelenz"))
+            .unwrap();
+        let stream = "2950158\tRur\tRur\t\t51.0\t6.0\tH\tSTM\tDE\t\t\t\t\t\t0\t\t40\tEurope/Berlin\t2023-07-01";
+        let modifications = write_test_file(
+            "modifications-2023-07-02.txt",
+            &format!("{}\n{}\n", erkelenz.replace("44650", "1000000"), stream),
+        );
+
+        let changes = Changes::read_modifications(&modifications, &LoadOptions::default()).unwrap();
+        let report = gc.apply(changes.clone());
+        assert_eq!((report.inserted, report.updated), (0, 1));
+        assert!(gc.get(2950158).is_none());
+        assert_eq!(gc.find("erkelenz", 1)[0].population, Some(1000000));
+
+        let report = gc.apply(Changes {
+            insert_new: true,
+            ..changes
+        });
+        assert_eq!((report.inserted, report.updated), (1, 1));
+        assert_eq!(gc.get(2950158).unwrap().feature_code, "STM");
+
+        let options = LoadOptions {
+            feature_classes: vec![String::from("P")],
+            ..Default::default()
+        };
+        let report = gc.apply_modifications(&modifications, &options).unwrap();
+        assert_eq!(report.deleted, 1);
+        assert!(gc.get(2950158).is_none());
+        assert_consistent(&gc);
+    }
+}
@@ -1,12 +1,14 @@
 use crate::errors::Error::ConfigurationError;
 use crate::Result;
-use serde::Deserialize;
+use geocoder::{DistanceMethod, LoadOptions, Schema};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
 use serde_with::{serde_as, DisplayFromStr};
 use std::net::SocketAddr;
 use tracing::Level;
 
 #[serde_as]
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Configuration {
     #[serde_as(as = "DisplayFromStr")]
     #[serde(default = "default_loglevel")]
@@ -17,8 +19,47 @@ pub struct Configuration {
     pub data_file: String,
     #[serde(default = "default_watch_for_changes")]
     pub watch_for_changes: bool,
+    #[serde(default)]
+    pub insert_new_places: bool,
     #[serde(default = "default_allow_origin")]
     pub allow_origin: String,
+    #[serde(default)]
+    pub skip_invalid_rows: bool,
+    pub admin1_file: Option<String>,
+    pub admin2_file: Option<String>,
+    pub country_info_file: Option<String>,
+    pub alternate_names_file: Option<String>,
+    pub postal_codes_file: Option<String>,
+    pub country_boundaries_file: Option<String>,
+    pub admin1_boundaries_file: Option<String>,
+    pub timezone_boundaries_file: Option<String>,
+    #[serde(default)]
+    pub languages: Vec<String>,
+    #[serde(default)]
+    pub not_found_if_empty: bool,
+    #[serde(default)]
+    pub distance_method: DistanceMethod,
+    #[serde(default)]
+    pub feature_classes: Vec<String>,
+    #[serde(default)]
+    pub feature_codes: Vec<String>,
+    #[serde(default)]
+    pub country_codes: Vec<String>,
+    pub min_population: Option<u32>,
+    #[serde(default)]
+    pub drop_alternatenames: bool,
+    #[serde(default)]
+    pub drop_dem: bool,
+    #[serde(default = "default_csv_delimiter", deserialize_with = "delimiter")]
+    pub csv_delimiter: u8,
+    #[serde(default = "default_csv_headers")]
+    pub csv_headers: bool,
+    #[serde(default, deserialize_with = "column_mapping")]
+    pub csv_columns: Vec<(String, String)>,
+    #[serde(default)]
+    pub csv_passthrough: Vec<String>,
+    #[serde(default, deserialize_with = "comment")]
+    pub csv_comment: Option<u8>,
 }
 
 fn default_loglevel() -> Level {
@@ -34,7 +75,53 @@ fn default_watch_for_changes() -> bool {
     true
 }
 fn default_allow_origin() -> String {
-    return String::from("*");
+    String::from("*")
+}
+fn default_csv_delimiter() -> u8 {
+    b','
+}
+fn default_csv_headers() -> bool {
+    true
+}
+
+/// A single ASCII character, or `tab`.
+fn delimiter<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<u8, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    match value.as_str() {
+        "tab" | "\\t" | "\t" => Ok(b'\t'),
+        _ if value.len() == 1 && value.is_ascii() => Ok(value.as_bytes()[0]),
+        _ => Err(D::Error::custom(format!("invalid delimiter '{}'", value))),
+    }
+}
+
+/// A single ASCII character starting comment rows.
+fn comment<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<u8>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    match value.as_bytes() {
+        [comment] if comment.is_ascii() => Ok(Some(*comment)),
+        _ => Err(D::Error::custom(format!(
+            "invalid comment character '{}'",
+            value
+        ))),
+    }
+}
+
+/// Comma separated `field=column` pairs.
+fn column_mapping<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<(String, String)>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|pair| match pair.split_once('=') {
+            Some((field, column)) => Ok((field.trim().to_string(), column.trim().to_string())),
+            None => Err(D::Error::custom(format!(
+                "expected field=column, got '{}'",
+                pair
+            ))),
+        })
+        .collect()
 }
 
 impl Configuration {
@@ -43,4 +130,90 @@ impl Configuration {
             .from_env::<Configuration>()
             .map_err(ConfigurationError)
     }
+
+    /// Rows of the data file to load, see [`LoadOptions`].
+    pub fn load_options(&self) -> LoadOptions {
+        LoadOptions {
+            feature_classes: self.feature_classes.clone(),
+            feature_codes: self.feature_codes.clone(),
+            country_codes: self.country_codes.clone(),
+            min_population: self.min_population,
+            drop_alternatenames: self.drop_alternatenames,
+            drop_dem: self.drop_dem,
+            schema: self.schema(),
+            skip_invalid_rows: self.skip_invalid_rows,
+        }
+    }
+
+    /// Layout of a custom data file, if any columns are mapped.
+    fn schema(&self) -> Option<Schema> {
+        if self.csv_columns.is_empty() {
+            return None;
+        }
+        Some(Schema {
+            delimiter: self.csv_delimiter,
+            has_headers: self.csv_headers,
+            columns: self.csv_columns.clone(),
+            passthrough: self.csv_passthrough.clone(),
+            comment: self.csv_comment,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_load_options_from_environment() {
+        let vars = [
+            ("GEOCODER_FEATURE_CLASSES", "P,A"),
+            ("GEOCODER_COUNTRY_CODES", "DE"),
+            ("GEOCODER_MIN_POPULATION", "1000"),
+            ("GEOCODER_DROP_ALTERNATENAMES", "true"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        let config: Configuration = envy::prefixed("GEOCODER_").from_iter(vars).unwrap();
+
+        let options = config.load_options();
+        assert_eq!(options.feature_classes, vec!["P", "A"]);
+        assert!(options.feature_codes.is_empty());
+        assert_eq!(options.country_codes, vec!["DE"]);
+        assert_eq!(options.min_population, Some(1000));
+        assert!(options.drop_alternatenames);
+        assert!(!options.drop_dem);
+        assert!(!options.skip_invalid_rows);
+        assert_eq!(options.schema, None);
+    }
+
+    #[test]
+    fn reads_schema_from_environment() {
+        let vars = [
+            ("GEOCODER_CSV_DELIMITER", "tab"),
+            (
+                "GEOCODER_CSV_COLUMNS",
+                "name=Store,latitude=Lat,longitude=Lon",
+            ),
+            ("GEOCODER_CSV_PASSTHROUGH", "Opening hours,Phone"),
+            ("GEOCODER_CSV_COMMENT", "#"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        let config: Configuration = envy::prefixed("GEOCODER_").from_iter(vars).unwrap();
+
+        let schema = config.load_options().schema.unwrap();
+        assert_eq!(schema.delimiter, b'\t');
+        assert!(schema.has_headers);
+        assert_eq!(
+            schema.columns[2],
+            (String::from("longitude"), String::from("Lon"))
+        );
+        assert_eq!(schema.passthrough, vec!["Opening hours", "Phone"]);
+        assert_eq!(schema.comment, Some(b'#'));
+
+        let vars = [("GEOCODER_CSV_COLUMNS", "name")]
+            .map(|(key, value)| (key.to_string(), value.to_string()));
+        assert!(envy::prefixed("GEOCODER_")
+            .from_iter::<_, Configuration>(vars)
+            .is_err());
+    }
 }
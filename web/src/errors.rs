@@ -2,6 +2,7 @@ use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
 use std::sync::TryLockError;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, PartialEq, thiserror::Error)]
 pub enum Error {
     #[error("invalid configuration: {0}")]
@@ -9,6 +10,21 @@ pub enum Error {
 
     #[error("please try again in a few seconds")]
     LockError(),
+
+    #[error("no places found")]
+    NotFoundError(),
+
+    #[error("no place with id {0}")]
+    PlaceNotFoundError(u32),
+
+    #[error("timestamp {0} is out of range")]
+    InvalidTimestampError(i64),
+
+    #[error("{0} isn't supported with a memory-mapped data file")]
+    NotSupportedError(&'static str),
+
+    #[error("no postal code file is configured")]
+    PostalCodesNotConfiguredError(),
 }
 
 impl<R> From<TryLockError<R>> for Error {
@@ -26,6 +42,15 @@ impl IntoResponse for Error {
                 self.to_string(),
             )
                 .into_response(),
+            Error::NotFoundError() | Error::PlaceNotFoundError(_) => {
+                (StatusCode::NOT_FOUND, self.to_string()).into_response()
+            }
+            Error::InvalidTimestampError(_) => {
+                (StatusCode::BAD_REQUEST, self.to_string()).into_response()
+            }
+            Error::NotSupportedError(_) | Error::PostalCodesNotConfiguredError() => {
+                (StatusCode::NOT_IMPLEMENTED, self.to_string()).into_response()
+            }
             _ => (StatusCode::INTERNAL_SERVER_ERROR, self).into_response(),
         }
     }
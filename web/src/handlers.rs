@@ -1,20 +1,143 @@
+use crate::errors::Error;
 use crate::{Result, SharedState};
-use axum::extract::{Query, State};
+use axum::extract::{Path, Query, State};
+use axum::http::header::ACCEPT_LANGUAGE;
+use axum::http::HeaderMap;
 use axum::Json;
-use geocoder::City;
-use geojson::{Feature, FeatureCollection, GeoJson, Geometry, JsonObject, Value};
+use chrono::{TimeZone, Utc};
+use geocoder::{
+    Boundaries, City, CityRef, Country, Filter, Float, LocalTime, PostalCode, ReverseGeocoder,
+};
+use geojson::{Feature, FeatureCollection, GeoJson, Geometry, JsonObject, JsonValue, Value};
 use serde::Deserialize;
+use serde_with::formats::CommaSeparator;
+use serde_with::{serde_as, StringWithSeparator};
+use std::borrow::Cow;
+use std::cmp::{Ordering, Reverse};
 
+#[serde_as]
 #[derive(Debug, Default, Deserialize)]
 pub struct GeocodeParameters {
-    lat: f32,
-    lng: f32,
+    lat: Float,
+    lng: Float,
     details: Option<bool>,
     results: Option<usize>,
+    radius: Option<Float>,
+    sort: Option<SortOrder>,
+    feature_class: Option<String>,
+    #[serde_as(as = "Option<StringWithSeparator::<CommaSeparator, String>>")]
+    #[serde(default)]
+    feature_code: Option<Vec<String>>,
+    min_population: Option<u32>,
+    #[serde_as(as = "Option<StringWithSeparator::<CommaSeparator, String>>")]
+    #[serde(default)]
+    country: Option<Vec<String>>,
+    max_distance: Option<Float>,
+    unit: Option<DistanceUnit>,
+    precision: Option<u32>,
+    lang: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct BoundingBoxParameters {
+    min_lat: Float,
+    min_lng: Float,
+    max_lat: Float,
+    max_lng: Float,
+    details: Option<bool>,
+    results: Option<usize>,
+    lang: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Distance,
+    Population,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DistanceUnit {
+    M,
+    #[default]
+    Km,
+    Mi,
+}
+
+impl DistanceUnit {
+    fn metres(&self) -> f64 {
+        match self {
+            DistanceUnit::M => 1.0,
+            DistanceUnit::Km => 1000.0,
+            DistanceUnit::Mi => 1609.344,
+        }
+    }
+
+    /// Convert a distance in metres to this unit, rounded to `precision` decimal places.
+    ///
+    /// Distances rounded to whole numbers are returned as integers.
+    fn format(&self, metres: f64, precision: u32) -> JsonValue {
+        let value = metres / self.metres();
+        if precision == 0 {
+            return (value.round() as u64).into();
+        }
+        let factor = 10f64.powi(precision.min(MAX_PRECISION) as i32);
+        ((value * factor).round() / factor).into()
+    }
+}
+
+/// Maximum number of decimal places for distances, more would only add noise
+const MAX_PRECISION: u32 = 6;
+
+/// Maximum number of nearest places returned without a radius
+const MAX_RESULTS: usize = 1000;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchParameters {
+    q: String,
+    exact: Option<bool>,
+    details: Option<bool>,
+    results: Option<usize>,
+    lang: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AutocompleteParameters {
+    q: String,
+    lat: Option<Float>,
+    lng: Option<Float>,
+    country: Option<String>,
+    details: Option<bool>,
+    results: Option<usize>,
+    lang: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PlaceParameters {
+    lang: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PostalCodeParameters {
+    lat: Float,
+    lng: Float,
+    results: Option<usize>,
+    unit: Option<DistanceUnit>,
+    precision: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TimezoneParameters {
+    lat: Float,
+    lng: Float,
+    timestamp: Option<i64>,
 }
 
 pub async fn geocode(
     State(state): State<SharedState>,
+    headers: HeaderMap,
     Query(pos): Query<GeocodeParameters>,
 ) -> Result<Json<GeoJson>> {
     let GeocodeParameters {
@@ -22,51 +145,366 @@ pub async fn geocode(
         lng,
         details,
         results,
+        radius,
+        sort,
+        feature_class,
+        feature_code,
+        min_population,
+        country,
+        max_distance,
+        unit,
+        precision,
+        lang,
     } = pos;
+    let languages = languages(lang, &headers);
+
+    let filter = Filter {
+        feature_class,
+        feature_codes: feature_code.unwrap_or_default(),
+        min_population,
+        country_codes: country.unwrap_or_default(),
+    };
+
+    let radius = radius.map(|km| max_distance.map_or(km, |max| km.min(max)));
+    let nearest = results.unwrap_or(1).min(MAX_RESULTS);
+    let gc = state.geocoder.try_read()?;
+    let mapped = state.mapped.try_read()?;
+    let found: Vec<(f64, Cow<City>)> = match mapped.as_ref() {
+        Some(mapped) => {
+            let mut found = match radius {
+                Some(km) => {
+                    let mut found = mapped.within_radius(lat, lng, km);
+                    found.retain(|(_, c)| filter.matches_ref(c));
+                    found
+                }
+                None => mapped.search_filtered(lat, lng, nearest, max_distance, &filter),
+            };
+            select(&mut found, sort, results, CityRef::population);
+            found
+                .into_iter()
+                .map(|(d, c)| (d, Cow::Owned(c.into())))
+                .collect()
+        }
+        None => {
+            let mut found = match radius {
+                Some(km) => {
+                    let mut found = gc.within_radius(lat, lng, km);
+                    found.retain(|(_, c)| filter.matches(c));
+                    found
+                }
+                None => gc.search_filtered(lat, lng, nearest, max_distance, &filter),
+            };
+            select(&mut found, sort, results, |c| c.population);
+            found
+                .into_iter()
+                .map(|(d, c)| (d, Cow::Borrowed(c)))
+                .collect()
+        }
+    };
+    if found.is_empty() && state.not_found_if_empty {
+        return Err(Error::NotFoundError());
+    }
+
+    let features: Vec<Feature> = found
+        .iter()
+        .map(|(d, c)| {
+            let distance = unit.unwrap_or_default().format(*d, precision.unwrap_or(0));
+            to_feature(&gc, c, Some(distance), details.unwrap_or(false), &languages)
+        })
+        .collect();
+
+    let feature_collection = FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: boundaries_to_json(&gc, lat, lng),
+    };
+    Ok(Json(GeoJson::from(feature_collection)))
+}
+
+pub async fn search(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(params): Query<SearchParameters>,
+) -> Result<Json<GeoJson>> {
+    let SearchParameters {
+        q,
+        exact,
+        details,
+        results,
+        lang,
+    } = params;
+    let languages = languages(lang, &headers);
+
+    not_supported_if_mapped(&state, "searching by name")?;
+    let gc = state.geocoder.try_read()?;
+    let results = if exact.unwrap_or(false) {
+        gc.find_exact(&q, results.unwrap_or(1))
+    } else {
+        gc.find(&q, results.unwrap_or(1))
+    };
+
+    let features: Vec<Feature> = results
+        .iter()
+        .map(|c| to_feature(&gc, c, None, details.unwrap_or(false), &languages))
+        .collect();
+
+    Ok(Json(to_geojson(features)))
+}
+
+pub async fn autocomplete(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(params): Query<AutocompleteParameters>,
+) -> Result<Json<GeoJson>> {
+    let AutocompleteParameters {
+        q,
+        lat,
+        lng,
+        country,
+        details,
+        results,
+        lang,
+    } = params;
+    let languages = languages(lang, &headers);
 
-    let gc = state.try_read()?;
-    let results = gc.search(lat, lng, results.unwrap_or(1));
+    not_supported_if_mapped(&state, "autocompletion")?;
+    let gc = state.geocoder.try_read()?;
+    let results = results.unwrap_or(10).min(MAX_RESULTS);
+    let results = gc.autocomplete(&q, results, country.as_deref(), lat.zip(lng));
 
     let features: Vec<Feature> = results
         .iter()
-        .map(|(d, c)| to_feature(&c, *d, details.unwrap_or(false)))
+        .map(|c| to_feature(&gc, c, None, details.unwrap_or(false), &languages))
         .collect();
 
+    Ok(Json(to_geojson(features)))
+}
+
+pub async fn bbox(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(params): Query<BoundingBoxParameters>,
+) -> Result<Json<GeoJson>> {
+    let BoundingBoxParameters {
+        min_lat,
+        min_lng,
+        max_lat,
+        max_lng,
+        details,
+        results,
+        lang,
+    } = params;
+    let languages = languages(lang, &headers);
+
+    let gc = state.geocoder.try_read()?;
+    let mapped = state.mapped.try_read()?;
+    let found: Vec<Cow<City>> = match mapped.as_ref() {
+        Some(mapped) => {
+            let mut found = mapped.within_bbox(min_lat, min_lng, max_lat, max_lng);
+            found.sort_by_key(|c| Reverse(c.population().unwrap_or(0)));
+            found.truncate(results.unwrap_or(usize::MAX));
+            found.into_iter().map(|c| Cow::Owned(c.into())).collect()
+        }
+        None => {
+            let mut found = gc.within_bbox(min_lat, min_lng, max_lat, max_lng);
+            found.sort_by_key(|c| Reverse(c.population.unwrap_or(0)));
+            found.truncate(results.unwrap_or(usize::MAX));
+            found.into_iter().map(Cow::Borrowed).collect()
+        }
+    };
+
+    let features: Vec<Feature> = found
+        .iter()
+        .map(|c| to_feature(&gc, c, None, details.unwrap_or(false), &languages))
+        .collect();
+
+    Ok(Json(to_geojson(features)))
+}
+
+/// The place with the given GeoNames id, as a single feature with details.
+pub async fn place(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Path(id): Path<u32>,
+    Query(params): Query<PlaceParameters>,
+) -> Result<Json<GeoJson>> {
+    let languages = languages(params.lang, &headers);
+
+    not_supported_if_mapped(&state, "looking up places by id")?;
+    let gc = state.geocoder.try_read()?;
+    let Some(city) = gc.get(id) else {
+        return Err(Error::PlaceNotFoundError(id));
+    };
+
+    Ok(Json(GeoJson::from(to_feature(
+        &gc, city, None, true, &languages,
+    ))))
+}
+
+pub async fn postal_code(
+    State(state): State<SharedState>,
+    Query(params): Query<PostalCodeParameters>,
+) -> Result<Json<GeoJson>> {
+    let PostalCodeParameters {
+        lat,
+        lng,
+        results,
+        unit,
+        precision,
+    } = params;
+
+    let postal_codes = state.postal_codes.try_read()?;
+    let Some(gc) = postal_codes.as_ref() else {
+        return Err(Error::PostalCodesNotConfiguredError());
+    };
+    let found = gc.search(lat, lng, results.unwrap_or(1).min(MAX_RESULTS));
+    if found.is_empty() && state.not_found_if_empty {
+        return Err(Error::NotFoundError());
+    }
+
+    let features: Vec<Feature> = found
+        .iter()
+        .map(|(d, postal_code)| {
+            let distance = unit.unwrap_or_default().format(*d, precision.unwrap_or(0));
+            postal_code_to_feature(postal_code, distance)
+        })
+        .collect();
+
+    Ok(Json(to_geojson(features)))
+}
+
+/// Sort the places by descending population if requested and keep at most `results` of them.
+///
+/// Sorting before limiting returns the most populous places within a radius.
+fn select<C>(
+    found: &mut Vec<(f64, C)>,
+    sort: Option<SortOrder>,
+    results: Option<usize>,
+    population: impl Fn(&C) -> Option<u32>,
+) {
+    if sort == Some(SortOrder::Population) {
+        found.sort_by_key(|(_, c)| Reverse(population(c).unwrap_or(0)));
+    }
+    if let Some(max) = results {
+        found.truncate(max);
+    }
+}
+
+/// Fails if the data file is memory-mapped, which only supports searching by coordinates.
+fn not_supported_if_mapped(state: &SharedState, what: &'static str) -> Result<()> {
+    match *state.mapped.try_read()? {
+        Some(_) => Err(Error::NotSupportedError(what)),
+        None => Ok(()),
+    }
+}
+
+/// Preferred languages of the client: the comma separated `lang` parameter or, without it, the
+/// Accept-Language header.
+fn languages(lang: Option<String>, headers: &HeaderMap) -> Vec<String> {
+    if let Some(lang) = lang {
+        return lang
+            .split(',')
+            .map(str::trim)
+            .filter(|lang| !lang.is_empty())
+            .map(String::from)
+            .collect();
+    }
+    match headers.get(ACCEPT_LANGUAGE).map(|value| value.to_str()) {
+        Some(Ok(header)) => accept_language(header),
+        _ => vec![],
+    }
+}
+
+/// Languages of an Accept-Language header, e.g. `de-CH, de;q=0.9, en;q=0.8`, by descending quality.
+fn accept_language(header: &str) -> Vec<String> {
+    let mut languages: Vec<(f32, &str)> = header
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';').map(str::trim);
+            let tag = parts.next()?;
+            let quality = match parts.find_map(|param| param.strip_prefix("q=")) {
+                Some(quality) => quality.parse().ok()?,
+                None => 1.0,
+            };
+            (!tag.is_empty() && tag != "*" && quality > 0.0).then_some((quality, tag))
+        })
+        .collect();
+    languages.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+    languages
+        .into_iter()
+        .map(|(_, tag)| tag.to_string())
+        .collect()
+}
+
+fn to_geojson(features: Vec<Feature>) -> GeoJson {
     let feature_collection = FeatureCollection {
         bbox: None,
         features,
         foreign_members: None,
     };
 
-    let serialized = GeoJson::from(feature_collection);
-
-    Ok(Json(serialized))
+    GeoJson::from(feature_collection)
 }
 
-fn to_feature(city: &City, distance: u32, include_details: bool) -> Feature {
+fn to_feature(
+    gc: &ReverseGeocoder,
+    city: &City,
+    distance: Option<JsonValue>,
+    include_details: bool,
+    languages: &[String],
+) -> Feature {
+    let admin1_name = gc.admin1(city).map(|a| a.name.clone());
+    let admin2_name = gc.admin2(city).map(|a| a.name.clone());
+    let country = gc.country(city).map(country_to_json);
     let city = city.clone();
 
-    let point = Value::Point(vec![city.longitude as f64, city.latitude as f64]);
+    let (lat, lng) = city.lat_lng();
+    let point = Value::Point(vec![lng, lat]);
 
     let mut properties = JsonObject::new();
-    properties.insert(String::from("distanceToQuery"), distance.into());
-    properties.insert(String::from("title"), city.name.into());
+    if let Some(distance) = distance {
+        properties.insert(String::from("distanceToQuery"), distance);
+    }
+    let title = gc.localized_name(&city, languages).unwrap_or(city.name());
+    properties.insert(String::from("title"), title.into());
+    if !languages.is_empty() {
+        properties.insert(String::from("defaultName"), city.name().into());
+    }
+    if let Some(country) = country {
+        properties.insert(String::from("country"), country.into());
+    }
+    if !city.properties.is_empty() {
+        let extra: JsonObject = city
+            .properties
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.into()))
+            .collect();
+        properties.insert(String::from("extra"), extra.into());
+    }
 
     if include_details {
-        properties.insert(String::from("featureCode"), city.feature_code.into());
-        properties.insert(String::from("countryCode"), city.country_code.into());
-        properties.insert(String::from("cc2"), city.cc2.into());
-        properties.insert(String::from("admin1Code"), city.admin1_code.into());
-        properties.insert(String::from("admin2Code"), city.admin2_code.into());
-        properties.insert(String::from("admin3Code"), city.admin3_code.into());
-        properties.insert(String::from("admin4Code"), city.admin4_code.into());
+        properties.insert(
+            String::from("featureCode"),
+            city.feature_code.as_str().into(),
+        );
+        properties.insert(
+            String::from("countryCode"),
+            city.country_code.as_str().into(),
+        );
+        properties.insert(String::from("cc2"), city.cc2.as_str().into());
+        properties.insert(String::from("admin1Code"), city.admin1_code.as_str().into());
+        properties.insert(String::from("admin2Code"), city.admin2_code.as_str().into());
+        properties.insert(String::from("admin3Code"), city.admin3_code.as_ref().into());
+        properties.insert(String::from("admin4Code"), city.admin4_code.as_ref().into());
+        properties.insert(String::from("admin1Name"), admin1_name.into());
+        properties.insert(String::from("admin2Name"), admin2_name.into());
         properties.insert(String::from("population"), city.population.into());
         properties.insert(String::from("elevation"), city.elevation.into());
-        properties.insert(String::from("dem"), city.dem.into());
-        properties.insert(String::from("timezone"), city.timezone.into());
+        properties.insert(String::from("dem"), city.dem.as_ref().into());
+        properties.insert(String::from("timezone"), city.timezone.as_str().into());
         properties.insert(
             String::from("modificationDate"),
-            city.modification_date.into(),
+            city.modification_date.as_ref().into(),
         );
     }
 
@@ -82,49 +520,212 @@ fn to_feature(city: &City, distance: u32, include_details: bool) -> Feature {
     }
 }
 
+pub async fn timezone(
+    State(state): State<SharedState>,
+    Query(params): Query<TimezoneParameters>,
+) -> Result<Json<JsonObject>> {
+    let TimezoneParameters {
+        lat,
+        lng,
+        timestamp,
+    } = params;
+
+    let time = match timestamp {
+        Some(timestamp) => Utc
+            .timestamp_opt(timestamp, 0)
+            .single()
+            .ok_or(Error::InvalidTimestampError(timestamp))?,
+        None => Utc::now(),
+    };
+
+    let gc = state.geocoder.try_read()?;
+    let mapped = state.mapped.try_read()?;
+    let timezone = match mapped.as_ref() {
+        Some(mapped) => match gc.timezone_boundaries().containing(lat, lng) {
+            Some(boundary) => Some(boundary.code.as_str()),
+            None => mapped.timezone(lat, lng),
+        },
+        None => gc.timezone(lat, lng),
+    };
+    let Some(local_time) = timezone.and_then(|timezone| LocalTime::at(timezone, time)) else {
+        return Err(Error::NotFoundError());
+    };
+
+    let mut object = JsonObject::new();
+    object.insert(String::from("timezone"), local_time.timezone.into());
+    object.insert(String::from("utcOffset"), local_time.utc_offset.into());
+    object.insert(String::from("dst"), local_time.dst.into());
+    object.insert(
+        String::from("localTime"),
+        local_time.time.to_rfc3339().into(),
+    );
+    Ok(Json(object))
+}
+
+/// Country and admin1 area containing the query point, `None` if no boundaries are loaded.
+fn boundaries_to_json(gc: &ReverseGeocoder, lat: Float, lng: Float) -> Option<JsonObject> {
+    let layers = [
+        ("country", gc.country_boundaries()),
+        ("admin1", gc.admin1_boundaries()),
+    ];
+    let mut object = JsonObject::new();
+    for (layer, boundaries) in layers.iter().filter(|(_, b)| !b.is_empty()) {
+        object.insert(layer.to_string(), boundary_to_json(boundaries, lat, lng));
+    }
+    if object.is_empty() {
+        return None;
+    }
+    let mut foreign_members = JsonObject::new();
+    foreign_members.insert(String::from("boundaries"), object.into());
+    Some(foreign_members)
+}
+
+fn boundary_to_json(boundaries: &Boundaries, lat: Float, lng: Float) -> JsonValue {
+    match boundaries.containing(lat, lng) {
+        Some(boundary) => {
+            let mut object = JsonObject::new();
+            object.insert(String::from("code"), boundary.code.clone().into());
+            object.insert(String::from("name"), boundary.name.clone().into());
+            object.into()
+        }
+        None => JsonValue::Null,
+    }
+}
+
+fn postal_code_to_feature(postal_code: &PostalCode, distance: JsonValue) -> Feature {
+    let (lat, lng) = postal_code.lat_lng();
+    let point = Value::Point(vec![lng, lat]);
+
+    let mut properties = JsonObject::new();
+    properties.insert(String::from("distanceToQuery"), distance);
+    properties.insert(
+        String::from("title"),
+        postal_code.postal_code.clone().into(),
+    );
+    properties.insert(
+        String::from("placeName"),
+        postal_code.place_name.clone().into(),
+    );
+    properties.insert(
+        String::from("countryCode"),
+        postal_code.country_code.as_str().into(),
+    );
+    let admin = [
+        (&postal_code.admin_name1, &postal_code.admin_code1),
+        (&postal_code.admin_name2, &postal_code.admin_code2),
+        (&postal_code.admin_name3, &postal_code.admin_code3),
+    ];
+    for (level, (name, code)) in admin.iter().enumerate() {
+        let level = level + 1;
+        properties.insert(format!("admin{}Name", level), name.as_str().into());
+        properties.insert(format!("admin{}Code", level), code.as_str().into());
+    }
+    properties.insert(String::from("accuracy"), postal_code.accuracy.into());
+
+    Feature {
+        bbox: None,
+        geometry: Some(Geometry::new(point)),
+        id: None,
+        properties: Some(properties),
+        foreign_members: None,
+    }
+}
+
+fn country_to_json(country: &Country) -> JsonObject {
+    let mut object = JsonObject::new();
+    object.insert(String::from("name"), country.name.clone().into());
+    object.insert(String::from("iso"), country.iso.clone().into());
+    object.insert(String::from("iso3"), country.iso3.clone().into());
+    object.insert(String::from("continent"), country.continent.clone().into());
+    object.insert(String::from("capital"), country.capital.clone().into());
+    object.insert(
+        String::from("currencyCode"),
+        country.currency_code.clone().into(),
+    );
+    object.insert(
+        String::from("currencyName"),
+        country.currency_name.clone().into(),
+    );
+    object.insert(
+        String::from("languages"),
+        country.languages().collect::<Vec<_>>().into(),
+    );
+    object.insert(
+        String::from("neighbours"),
+        country.neighbours().collect::<Vec<_>>().into(),
+    );
+    object
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::errors::Error::LockError;
-    use geocoder::ReverseGeocoder;
+    use crate::errors::Error::{
+        InvalidTimestampError, LockError, NotFoundError, NotSupportedError, PlaceNotFoundError,
+        PostalCodesNotConfiguredError,
+    };
+    use crate::AppState;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use geocoder::{Names, Properties, ReverseGeocoder};
     use std::sync::{Arc, RwLock};
     use tracing_test::traced_test;
 
     fn test_city() -> City {
         City {
             id: 0,
-            name: "Erkelenz".to_string(),
-            asciiname: "erkelenz".to_string(),
-            alternatenames: "erkelenz".to_string(),
+            names: Names::new("Erkelenz", "erkelenz", "erkelenz"),
             latitude: 51.0,
             longitude: 6.0,
-            feature_class: "P".to_string(),
-            feature_code: "PPLA2".to_string(),
-            country_code: "DE".to_string(),
-            cc2: "DE".to_string(),
-            admin1_code: "".to_string(),
-            admin2_code: "".to_string(),
-            admin3_code: "".to_string(),
-            admin4_code: "".to_string(),
+            feature_class: "P".into(),
+            feature_code: "PPLA2".into(),
+            country_code: "DE".into(),
+            cc2: "DE".into(),
+            admin1_code: "".into(),
+            admin2_code: "".into(),
+            admin3_code: "".into(),
+            admin4_code: "".into(),
             population: None,
             elevation: None,
-            dem: "".to_string(),
-            timezone: "".to_string(),
-            modification_date: "".to_string(),
+            dem: "".into(),
+            timezone: "".into(),
+            modification_date: "".into(),
+            properties: Properties::default(),
         }
     }
 
+    /// Features of a successful response, which has to be a FeatureCollection.
+    fn features(result: Result<Json<GeoJson>>) -> Vec<Feature> {
+        let GeoJson::FeatureCollection(collection) = result.unwrap().0 else {
+            panic!("expected a FeatureCollection");
+        };
+        collection.features
+    }
+
+    /// Ids of the features of a successful response.
+    fn ids(result: Result<Json<GeoJson>>) -> Vec<u64> {
+        features(result)
+            .iter()
+            .map(|f| f.foreign_members.as_ref().unwrap()["id"].as_u64().unwrap())
+            .collect()
+    }
+
+    fn get_geocode(state: &SharedState, params: GeocodeParameters) -> Result<Json<GeoJson>> {
+        tokio_test::block_on(geocode(
+            State(state.clone()),
+            HeaderMap::new(),
+            Query(params),
+        ))
+    }
+
     #[test]
     #[traced_test]
     fn returns_error_when_geocoder_busy() {
-        let state = Arc::new(RwLock::new(ReverseGeocoder::default()));
+        let state = Arc::new(AppState::from(ReverseGeocoder::default()));
 
-        let _lock = state.write().unwrap();
+        let _lock = state.geocoder.write().unwrap();
 
-        let result = tokio_test::block_on(geocode(
-            State(state.clone()),
-            Query(GeocodeParameters::default()),
-        ));
+        let result = get_geocode(&state, GeocodeParameters::default());
 
         assert_eq!(LockError(), result.unwrap_err());
     }
@@ -134,13 +735,510 @@ mod tests {
     #[traced_test]
     fn returns_cities_without_details() {
         let erkelenz: City = test_city();
-        let state = Arc::new(RwLock::new(ReverseGeocoder::new(vec![erkelenz.clone()])));
+        let state = Arc::new(AppState::from(ReverseGeocoder::new(vec![erkelenz.clone()])));
         let query = GeocodeParameters::default();
 
-        let result = tokio_test::block_on(geocode(State(state), Query(query))).unwrap();
-
-        let city = result.0.first().unwrap();
-        let expected = to_feature(&erkelenz, 5511, false);
+        let features = features(get_geocode(&state, query));
+        let city = features.first().unwrap();
+        let expected = to_feature(
+            &state.geocoder.read().unwrap(),
+            &erkelenz,
+            Some(5699.into()),
+            false,
+            &[],
+        );
         assert_eq!(&expected, city);
     }
+
+    #[test]
+    #[traced_test]
+    fn returns_admin1_name_with_details() {
+        let admin1 = std::env::temp_dir().join("web-admin1CodesASCII.txt");
+        std::fs::write(
+            &admin1,
+            "DE.07\tNorth Rhine-Westphalia\tNorth Rhine-Westphalia\t2861876\n",
+        )
+        .unwrap();
+        let city = City {
+            admin1_code: "07".into(),
+            ..test_city()
+        };
+        let mut gc = ReverseGeocoder::new(vec![city]);
+        gc.load_admin1_codes(admin1.to_str().unwrap()).unwrap();
+        let state = Arc::new(AppState::from(gc));
+        let query = GeocodeParameters {
+            lat: 51.0,
+            lng: 6.0,
+            details: Some(true),
+            ..Default::default()
+        };
+
+        let features = features(get_geocode(&state, query));
+        let properties = features[0].properties.as_ref().unwrap();
+        assert_eq!(properties["admin1Code"], "07");
+        assert_eq!(properties["admin1Name"], "North Rhine-Westphalia");
+    }
+
+    #[test]
+    fn returns_coordinates_as_in_source_file() {
+        let city = City {
+            latitude: 78.2233,
+            longitude: 15.64689,
+            ..test_city()
+        };
+        let feature = to_feature(&ReverseGeocoder::default(), &city, None, false, &[]);
+        let geometry = feature.geometry.unwrap();
+        assert_eq!(geometry.value, Value::Point(vec![15.64689, 78.2233]));
+    }
+
+    #[test]
+    fn returns_passthrough_columns_as_properties() {
+        let city = City {
+            properties: Properties::from_iter([("openingHours", "8-20")]),
+            ..test_city()
+        };
+        let feature = to_feature(&ReverseGeocoder::default(), &city, None, false, &[]);
+        let properties = feature.properties.unwrap();
+        assert_eq!(properties["extra"]["openingHours"], "8-20");
+        assert_eq!(properties["title"], "Erkelenz");
+
+        let city = City {
+            properties: Properties::from_iter([("title", "Store 1")]),
+            ..test_city()
+        };
+        let feature = to_feature(&ReverseGeocoder::default(), &city, None, false, &[]);
+        let properties = feature.properties.unwrap();
+        assert_eq!(properties["extra"]["title"], "Store 1");
+        assert_eq!(properties["title"], "Erkelenz");
+
+        let feature = to_feature(&ReverseGeocoder::default(), &test_city(), None, false, &[]);
+        assert!(feature.properties.unwrap().get("extra").is_none());
+    }
+
+    #[test]
+    fn formats_distances() {
+        assert_eq!(DistanceUnit::Km.format(5699.4, 0), 6);
+        assert_eq!(DistanceUnit::Km.format(5699.4, 2), 5.7);
+        assert_eq!(DistanceUnit::M.format(5699.4, 1), 5699.4);
+        assert_eq!(DistanceUnit::Mi.format(1609.344, 3), 1.0);
+        assert_eq!(DistanceUnit::M.format(0.123456789, 20), 0.123457);
+    }
+
+    #[test]
+    #[traced_test]
+    fn returns_cities_within_radius() {
+        let city = |id, lat, population| City {
+            id,
+            latitude: lat,
+            longitude: 6.0,
+            population: Some(population),
+            ..test_city()
+        };
+        let state = Arc::new(AppState::from(ReverseGeocoder::new(vec![
+            city(1, 51.0, 1000),
+            city(2, 51.1, 5000),
+            city(3, 52.0, 100000),
+        ])));
+        let query = |sort, results| GeocodeParameters {
+            lat: 51.0,
+            lng: 6.0,
+            radius: Some(25.0),
+            sort,
+            results,
+            ..Default::default()
+        };
+        let found = |params| ids(get_geocode(&state, params));
+
+        assert_eq!(found(query(None, None)), vec![1, 2]);
+        assert_eq!(found(query(Some(SortOrder::Population), None)), vec![2, 1]);
+        assert_eq!(found(query(Some(SortOrder::Population), Some(1))), vec![2]);
+    }
+
+    #[test]
+    #[traced_test]
+    fn filters_cities() {
+        let city = |id, feature_code: &str, country_code: &str| City {
+            id,
+            feature_code: feature_code.into(),
+            country_code: country_code.into(),
+            ..test_city()
+        };
+        let state = Arc::new(AppState::from(ReverseGeocoder::new(vec![
+            city(1, "PPL", "DE"),
+            city(2, "PPLA", "NL"),
+            city(3, "PPLC", "DE"),
+        ])));
+        let query: GeocodeParameters =
+            serde_urlencoded::from_str("lat=51&lng=6&results=5&feature_code=PPLA,PPLC&country=DE")
+                .unwrap();
+
+        assert_eq!(ids(get_geocode(&state, query)), vec![3]);
+    }
+
+    #[test]
+    #[traced_test]
+    fn drops_cities_beyond_max_distance() {
+        let gc = || ReverseGeocoder::new(vec![test_city()]);
+        let query = || GeocodeParameters {
+            lat: 51.0,
+            lng: 8.0,
+            max_distance: Some(100.0),
+            ..Default::default()
+        };
+
+        let state = Arc::new(AppState::from(gc()));
+        assert!(features(get_geocode(&state, query())).is_empty());
+
+        let state = Arc::new(AppState {
+            not_found_if_empty: true,
+            ..AppState::from(gc())
+        });
+        assert_eq!(NotFoundError(), get_geocode(&state, query()).unwrap_err());
+    }
+
+    #[test]
+    #[traced_test]
+    fn returns_most_populous_cities_in_bbox() {
+        let city = |id, lng, population| City {
+            id,
+            latitude: 0.0,
+            longitude: lng,
+            population: Some(population),
+            ..test_city()
+        };
+        let state = Arc::new(AppState::from(ReverseGeocoder::new(vec![
+            city(1, 179.0, 1000),
+            city(2, -179.0, 5000),
+            city(3, 0.0, 100000),
+        ])));
+        let query = BoundingBoxParameters {
+            min_lat: -1.0,
+            min_lng: 178.0,
+            max_lat: 1.0,
+            max_lng: -178.0,
+            results: Some(1),
+            ..Default::default()
+        };
+
+        let result = tokio_test::block_on(bbox(State(state), HeaderMap::new(), Query(query)));
+
+        assert_eq!(ids(result), vec![2]);
+    }
+
+    #[test]
+    #[traced_test]
+    fn searches_cities_by_name() {
+        let erkelenz: City = test_city();
+        let state = Arc::new(AppState::from(ReverseGeocoder::new(vec![erkelenz.clone()])));
+        let query = SearchParameters {
+            q: String::from("ERKELENZ"),
+            ..Default::default()
+        };
+
+        let result =
+            tokio_test::block_on(search(State(state.clone()), HeaderMap::new(), Query(query)));
+
+        let expected = to_feature(&state.geocoder.read().unwrap(), &erkelenz, None, false, &[]);
+        assert_eq!(features(result), vec![expected]);
+    }
+
+    #[test]
+    fn prefers_lang_parameter_over_accept_language() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            ACCEPT_LANGUAGE,
+            "en;q=0.5, de-CH, *;q=0.1, de;q=0.9".parse().unwrap(),
+        );
+
+        assert_eq!(languages(None, &headers), vec!["de-CH", "de", "en"]);
+        assert_eq!(
+            languages(Some(String::from("ja, ar")), &headers),
+            vec!["ja", "ar"]
+        );
+        assert!(languages(None, &HeaderMap::new()).is_empty());
+    }
+
+    #[test]
+    #[traced_test]
+    fn returns_localized_titles() {
+        let names = std::env::temp_dir().join("web-alternateNamesV2.txt");
+        std::fs::write(&names, "1\t0\tja\tエルケレンツ\t1\t\t\t\t\t\n").unwrap();
+        let mut gc = ReverseGeocoder::new(vec![test_city()]);
+        gc.load_alternate_names(names.to_str().unwrap(), &["ja"])
+            .unwrap();
+        let state = Arc::new(AppState::from(gc));
+        let query = |lang: &str| SearchParameters {
+            q: String::from("Erkelenz"),
+            lang: Some(lang.to_string()),
+            ..Default::default()
+        };
+        let properties = |lang| {
+            let result = tokio_test::block_on(search(
+                State(state.clone()),
+                HeaderMap::new(),
+                Query(query(lang)),
+            ));
+            features(result)[0].properties.clone().unwrap()
+        };
+
+        let localized = properties("ja");
+        assert_eq!(localized["title"], "エルケレンツ");
+        assert_eq!(localized["defaultName"], "Erkelenz");
+        let default = properties("de,en");
+        assert_eq!(default["title"], "Erkelenz");
+        assert_eq!(default["defaultName"], "Erkelenz");
+    }
+
+    #[test]
+    #[traced_test]
+    fn returns_boundaries_containing_query_point() {
+        let boundaries = std::env::temp_dir().join("web_countries.geojson");
+        std::fs::write(
+            &boundaries,
+            r#"{"type":"FeatureCollection","features":[{"type":"Feature",
+                "properties":{"ISO_A2":"CH","NAME":"Switzerland"},
+                "geometry":{"type":"Polygon","coordinates":[[[7.5,47.4],[7.7,47.4],[7.7,47.56],[7.5,47.56],[7.5,47.4]]]}}]}"#,
+        )
+        .unwrap();
+        // The nearest city is in Germany, the query point in Switzerland
+        let mut gc = ReverseGeocoder::new(vec![test_city()]);
+        let query = |lat, lng| GeocodeParameters {
+            lat,
+            lng,
+            ..Default::default()
+        };
+        let foreign_members = |gc, params| {
+            let state = Arc::new(AppState::from(gc));
+            let result =
+                tokio_test::block_on(geocode(State(state), HeaderMap::new(), Query(params)));
+            let GeoJson::FeatureCollection(collection) = result.unwrap().0 else {
+                panic!("expected a FeatureCollection");
+            };
+            collection.foreign_members
+        };
+
+        assert_eq!(
+            foreign_members(ReverseGeocoder::new(vec![test_city()]), query(47.5, 7.6)),
+            None
+        );
+
+        gc.load_country_boundaries(boundaries.to_str().unwrap())
+            .unwrap();
+        let members = foreign_members(gc, query(47.5, 7.6)).unwrap();
+        assert_eq!(members["boundaries"]["country"]["code"], "CH");
+        assert_eq!(members["boundaries"]["country"]["name"], "Switzerland");
+        assert!(members["boundaries"].get("admin1").is_none());
+    }
+
+    //noinspection SpellCheckingInspection
+    #[test]
+    #[traced_test]
+    fn serves_memory_mapped_data_file() {
+        let city = |id, lat, population| City {
+            id,
+            latitude: lat,
+            longitude: 6.0,
+            population: Some(population),
+            timezone: "Europe/Berlin".into(),
+            ..test_city()
+        };
+        let file = std::env::temp_dir().join("web-cities.mmap");
+        let file = file.to_str().unwrap();
+        ReverseGeocoder::new(vec![city(1, 51.0, 1000), city(2, 51.1, 5000)])
+            .save_mapped(file)
+            .unwrap();
+        let state = Arc::new(AppState::from(ReverseGeocoder::default()));
+        *state.mapped.write().unwrap() = Some(geocoder::MappedGeocoder::open(file).unwrap());
+        let query = GeocodeParameters {
+            lat: 51.0,
+            lng: 6.0,
+            radius: Some(50.0),
+            sort: Some(SortOrder::Population),
+            results: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(ids(get_geocode(&state, query)), vec![2]);
+
+        let query = GeocodeParameters {
+            lat: 51.0,
+            lng: 6.0,
+            min_population: Some(2000),
+            ..Default::default()
+        };
+        assert_eq!(ids(get_geocode(&state, query)), vec![2]);
+
+        let query = BoundingBoxParameters {
+            min_lat: 50.9,
+            min_lng: 5.9,
+            max_lat: 51.05,
+            max_lng: 6.1,
+            ..Default::default()
+        };
+        let result =
+            tokio_test::block_on(bbox(State(state.clone()), HeaderMap::new(), Query(query)));
+        assert_eq!(ids(result), vec![1]);
+
+        let query = TimezoneParameters {
+            lat: 51.0,
+            lng: 6.0,
+            timestamp: Some(0),
+        };
+        let result = tokio_test::block_on(timezone(State(state.clone()), Query(query))).unwrap();
+        assert_eq!(result.0["timezone"], "Europe/Berlin");
+
+        let query = SearchParameters {
+            q: String::from("Erkelenz"),
+            ..Default::default()
+        };
+        let result = tokio_test::block_on(search(State(state), HeaderMap::new(), Query(query)));
+        assert_eq!(result.unwrap_err(), NotSupportedError("searching by name"));
+    }
+
+    #[test]
+    #[traced_test]
+    fn returns_timezone_and_local_time() {
+        let city = City {
+            timezone: "Europe/Berlin".into(),
+            ..test_city()
+        };
+        let state = Arc::new(AppState::from(ReverseGeocoder::new(vec![city])));
+        let query = |timestamp| TimezoneParameters {
+            lat: 51.0,
+            lng: 6.0,
+            timestamp,
+        };
+
+        let summer = Some(1688212800); // 2023-07-01T12:00:00Z
+        let result = tokio_test::block_on(timezone(State(state.clone()), Query(query(summer))));
+        let object = result.unwrap().0;
+        assert_eq!(object["timezone"], "Europe/Berlin");
+        assert_eq!(object["utcOffset"], 7200);
+        assert_eq!(object["dst"], true);
+        assert_eq!(object["localTime"], "2023-07-01T14:00:00+02:00");
+
+        let result = tokio_test::block_on(timezone(State(state.clone()), Query(query(None))));
+        assert!(result.unwrap().0["localTime"].is_string());
+
+        let result = tokio_test::block_on(timezone(State(state), Query(query(Some(i64::MAX)))));
+        let error = result.unwrap_err();
+        assert_eq!(InvalidTimestampError(i64::MAX), error);
+        assert_eq!(error.into_response().status(), StatusCode::BAD_REQUEST);
+
+        let state = Arc::new(AppState::from(ReverseGeocoder::default()));
+        let result = tokio_test::block_on(timezone(State(state.clone()), Query(query(None))));
+        assert_eq!(NotFoundError(), result.unwrap_err());
+        // The timestamp is checked even if no timezone is found
+        let result = tokio_test::block_on(timezone(State(state), Query(query(Some(i64::MIN)))));
+        assert_eq!(InvalidTimestampError(i64::MIN), result.unwrap_err());
+    }
+
+    //noinspection SpellCheckingInspection
+    #[test]
+    #[traced_test]
+    fn returns_nearest_postal_codes() {
+        let record = |postal_code: &str, place_name: &str, lat| PostalCode {
+            country_code: "DE".into(),
+            postal_code: postal_code.to_string(),
+            place_name: place_name.to_string(),
+            admin_name1: "Nordrhein-Westfalen".into(),
+            admin_code1: "NW".into(),
+            latitude: lat,
+            longitude: 6.3,
+            accuracy: Some(4),
+            ..Default::default()
+        };
+        let state = Arc::new(AppState {
+            postal_codes: RwLock::new(Some(ReverseGeocoder::new(vec![
+                record("41812", "Erkelenz", 51.08),
+                record("52525", "Heinsberg", 51.06),
+            ]))),
+            ..AppState::from(ReverseGeocoder::default())
+        });
+        let query = PostalCodeParameters {
+            lat: 51.081,
+            lng: 6.3,
+            results: Some(2),
+            unit: Some(DistanceUnit::M),
+            ..Default::default()
+        };
+
+        let result = tokio_test::block_on(postal_code(State(state), Query(query)));
+        let features = features(result);
+        let properties = features[0].properties.as_ref().unwrap();
+        assert_eq!(properties["title"], "41812");
+        assert_eq!(properties["placeName"], "Erkelenz");
+        assert_eq!(properties["admin1Name"], "Nordrhein-Westfalen");
+        assert_eq!(properties["admin2Name"], "");
+        assert_eq!(properties["distanceToQuery"], 111);
+        assert_eq!(features.len(), 2);
+    }
+
+    #[test]
+    #[traced_test]
+    fn returns_place_by_id() {
+        let erkelenz = City {
+            id: 2929622,
+            ..test_city()
+        };
+        let state = Arc::new(AppState::from(ReverseGeocoder::new(vec![erkelenz])));
+        let place = |id| {
+            tokio_test::block_on(place(
+                State(state.clone()),
+                HeaderMap::new(),
+                Path(id),
+                Query(PlaceParameters::default()),
+            ))
+        };
+
+        let GeoJson::Feature(feature) = place(2929622).unwrap().0 else {
+            panic!("expected a Feature");
+        };
+        let properties = feature.properties.as_ref().unwrap();
+        assert_eq!(properties["title"], "Erkelenz");
+        assert_eq!(properties["featureCode"], "PPLA2");
+        assert_eq!(feature.foreign_members.unwrap()["id"], 2929622);
+
+        let error = place(1).unwrap_err();
+        assert_eq!(error, PlaceNotFoundError(1));
+        assert_eq!(error.to_string(), "no place with id 1");
+    }
+
+    #[test]
+    #[traced_test]
+    fn returns_not_implemented_without_postal_codes() {
+        let state = Arc::new(AppState::from(ReverseGeocoder::default()));
+        let result = tokio_test::block_on(postal_code(
+            State(state),
+            Query(PostalCodeParameters::default()),
+        ));
+        let error = result.unwrap_err();
+        assert_eq!(PostalCodesNotConfiguredError(), error);
+        assert_eq!(error.into_response().status(), StatusCode::NOT_IMPLEMENTED);
+    }
+
+    #[test]
+    #[traced_test]
+    fn autocompletes_city_names() {
+        let erkelenz: City = test_city();
+        let state = Arc::new(AppState::from(ReverseGeocoder::new(vec![erkelenz.clone()])));
+        let query = |q: &str, country: Option<&str>| AutocompleteParameters {
+            q: q.to_string(),
+            country: country.map(String::from),
+            ..Default::default()
+        };
+
+        let result = tokio_test::block_on(autocomplete(
+            State(state.clone()),
+            HeaderMap::new(),
+            Query(query("erk", None)),
+        ));
+        assert_eq!(features(result).len(), 1);
+
+        let result = tokio_test::block_on(autocomplete(
+            State(state.clone()),
+            HeaderMap::new(),
+            Query(query("erk", Some("NL"))),
+        ));
+        assert!(features(result).is_empty());
+    }
 }
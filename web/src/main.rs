@@ -6,14 +6,12 @@ mod middleware;
 use axum::http::Method;
 use axum::routing::get;
 use axum::Router;
-use notify::event::DataChange::Content;
-use notify::event::ModifyKind::Data;
-use notify::EventKind::Modify;
-use notify::{Event, RecursiveMode, Watcher};
+use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
 use std::env;
 use std::net::SocketAddr;
-use std::path::Path;
-use std::sync::{Arc, RwLock};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
 use tokio::signal;
 use tower::ServiceBuilder;
 use tower_http::cors::{AllowOrigin, CorsLayer};
@@ -21,15 +19,304 @@ use tower_http::trace::TraceLayer;
 
 use crate::config::Configuration;
 use crate::errors::Error;
-use geocoder::ReverseGeocoder;
+use geocoder::{Changes, LoadOptions, MappedGeocoder, PostalCode, ReverseGeocoder};
 
 pub static VERSION: &str = env!("CARGO_PKG_VERSION");
 
-type SharedState = Arc<RwLock<ReverseGeocoder>>;
+/// State shared by all request handlers.
+///
+/// With a memory-mapped data file, the cities are searched in `mapped` and `geocoder` only holds
+/// the lookup tables (admin codes, country info, localized names and boundaries).
+pub struct AppState {
+    geocoder: RwLock<ReverseGeocoder>,
+    mapped: RwLock<Option<MappedGeocoder>>,
+    postal_codes: RwLock<Option<ReverseGeocoder<PostalCode>>>,
+    not_found_if_empty: bool,
+}
+
+impl From<ReverseGeocoder> for AppState {
+    fn from(gc: ReverseGeocoder) -> Self {
+        Self {
+            geocoder: RwLock::new(gc),
+            mapped: RwLock::new(None),
+            postal_codes: RwLock::new(None),
+            not_found_if_empty: false,
+        }
+    }
+}
+
+type SharedState = Arc<AppState>;
+
+/// Load the data file (TSV, snapshot or memory-mapped snapshot) and optional lookup tables,
+/// honoring the configured strictness and load options. When watching for changes, the daily
+/// diff files written since the data file are applied again.
+///
+/// A memory-mapped data file is returned separately, along with an empty geocoder holding the
+/// lookup tables.
+fn load(
+    config: &Configuration,
+) -> geocoder::errors::Result<(ReverseGeocoder, Option<MappedGeocoder>)> {
+    let mut mapped = None;
+    let mut gc = if geocoder::is_mapped(&config.data_file)? {
+        warn_if_load_options_ignored(config);
+        let mapped = mapped.insert(MappedGeocoder::open(&config.data_file)?);
+        mapped.set_distance_method(config.distance_method);
+        ReverseGeocoder::default()
+    } else if geocoder::is_snapshot(&config.data_file)? {
+        warn_if_load_options_ignored(config);
+        ReverseGeocoder::load_snapshot(&config.data_file)?
+    } else {
+        let options = config.load_options();
+        let (gc, report) = ReverseGeocoder::from_file_with_options(&config.data_file, &options)?;
+        tracing::info!("Loaded {}: {}", config.data_file, report);
+        gc
+    };
+    if config.watch_for_changes && mapped.is_none() {
+        apply_pending_diffs(&mut gc, config);
+    }
+    gc.set_distance_method(config.distance_method);
+    if let Some(file) = &config.admin1_file {
+        gc.load_admin1_codes(file)?;
+    }
+    if let Some(file) = &config.admin2_file {
+        gc.load_admin2_codes(file)?;
+    }
+    if let Some(file) = &config.country_info_file {
+        gc.load_country_info(file)?;
+    }
+    if let Some(file) = &config.alternate_names_file {
+        gc.load_alternate_names(file, &config.languages)?;
+    }
+    if let Some(file) = &config.country_boundaries_file {
+        gc.load_country_boundaries(file)?;
+    }
+    if let Some(file) = &config.admin1_boundaries_file {
+        gc.load_admin1_boundaries(file)?;
+    }
+    if let Some(file) = &config.timezone_boundaries_file {
+        gc.load_timezone_boundaries(file)?;
+    }
+    Ok((gc, mapped))
+}
+
+/// Returns `true` if load options that select or transform rows are configured. Snapshots are
+/// used as they were saved, so these don't apply to them.
+fn has_row_options(config: &Configuration) -> bool {
+    let options = LoadOptions {
+        skip_invalid_rows: false,
+        ..config.load_options()
+    };
+    options != LoadOptions::default()
+}
+
+fn warn_if_load_options_ignored(config: &Configuration) {
+    if has_row_options(config) {
+        tracing::warn!(
+            "{} is a snapshot, the configured filters, dropped columns and CSV schema are ignored",
+            config.data_file
+        );
+    }
+}
+
+/// Load the postal code file, if configured. Only the configured country codes and strictness
+/// apply, the other load options are meant for the data file.
+fn load_postal_codes(
+    config: &Configuration,
+) -> geocoder::errors::Result<Option<ReverseGeocoder<PostalCode>>> {
+    let Some(file) = &config.postal_codes_file else {
+        return Ok(None);
+    };
+    let options = LoadOptions {
+        country_codes: config.country_codes.clone(),
+        skip_invalid_rows: config.skip_invalid_rows,
+        ..Default::default()
+    };
+    let (gc, _) = ReverseGeocoder::from_postal_codes_file(file, &options)?;
+    Ok(Some(gc))
+}
+
+/// Replace the geocoder with a freshly loaded one. Keeps the current data if loading fails.
+fn reload(state: &SharedState, config: &Configuration) {
+    match load(config) {
+        Ok((gc, mapped)) => {
+            let mut geocoder = state
+                .geocoder
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            *state.mapped.write().unwrap_or_else(PoisonError::into_inner) = mapped;
+            *geocoder = gc;
+        }
+        Err(e) => tracing::error!("Unable to reload data file, keeping previous data: {}", e),
+    }
+}
+
+/// Replace the postal codes with freshly loaded ones. Keeps the current data if loading fails.
+fn reload_postal_codes(state: &SharedState, config: &Configuration) {
+    match load_postal_codes(config) {
+        Ok(postal_codes) => {
+            *state
+                .postal_codes
+                .write()
+                .unwrap_or_else(PoisonError::into_inner) = postal_codes
+        }
+        Err(e) => tracing::error!(
+            "Unable to reload postal code file, keeping previous data: {}",
+            e
+        ),
+    }
+}
+
+/// Apply a GeoNames daily diff file to the live geocoder. Keeps the current data if reading
+/// the file fails.
+///
+/// The file is read before locking the geocoder, so requests are only blocked while the
+/// changes are applied. Memory-mapped data files are read-only and aren't updated.
+fn apply_diff(state: &SharedState, config: &Configuration, diff_file: &Path) {
+    let filename = diff_file.to_string_lossy();
+    if state
+        .mapped
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .is_some()
+    {
+        tracing::warn!(
+            "Ignoring {}, memory-mapped data files can't be updated",
+            filename
+        );
+        return;
+    }
+    match read_diff(config, diff_file) {
+        Ok(changes) => {
+            let mut gc = state
+                .geocoder
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            let report = gc.apply(changes);
+            tracing::info!("Applied {}: {}", filename, report)
+        }
+        Err(e) => tracing::error!("Unable to apply diff file, keeping previous data: {}", e),
+    }
+}
+
+/// Read a GeoNames daily diff file with the configured load options.
+fn read_diff(config: &Configuration, diff_file: &Path) -> geocoder::errors::Result<Changes> {
+    let filename = diff_file.to_string_lossy();
+    let mut changes = if is_deletes_file(diff_file) {
+        Changes::read_deletes(&filename)?
+    } else {
+        Changes::read_modifications(&filename, &config.load_options())?
+    };
+    changes.insert_new |= config.insert_new_places;
+    Ok(changes)
+}
+
+/// Apply the daily diff files next to the data file which were written after it, so they
+/// aren't lost when the data file is loaded again. Files that can't be read are skipped.
+fn apply_pending_diffs(gc: &mut ReverseGeocoder, config: &Configuration) {
+    let diff_files = match pending_diff_files(&config.data_file) {
+        Ok(diff_files) => diff_files,
+        Err(e) => {
+            tracing::error!("Unable to look for diff files: {}", e);
+            return;
+        }
+    };
+    for diff_file in diff_files {
+        match read_diff(config, &diff_file) {
+            Ok(changes) => {
+                let report = gc.apply(changes);
+                tracing::info!("Applied {}: {}", diff_file.display(), report)
+            }
+            Err(e) => tracing::error!("Unable to apply diff file, skipping it: {}", e),
+        }
+    }
+}
+
+/// Returns the daily diff files in the directory of the data file which were modified after
+/// it, in the order they were published: by date, modifications before deletes.
+fn pending_diff_files(data_file: &str) -> std::io::Result<Vec<PathBuf>> {
+    let loaded = std::fs::metadata(data_file)?.modified()?;
+    let mut diff_files = vec![];
+    for entry in std::fs::read_dir(directory(data_file))? {
+        let entry = entry?;
+        let path = entry.path();
+        if is_diff_file(&path) && entry.metadata()?.modified()? > loaded {
+            diff_files.push(path);
+        }
+    }
+    diff_files.sort_by_key(|path| {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let date = name
+            .trim_start_matches("modifications-")
+            .trim_start_matches("deletes-");
+        (date.to_string(), is_deletes_file(path))
+    });
+    Ok(diff_files)
+}
+
+/// Returns `true` if the event means a file was written to or replaced.
+fn is_write(event: &Event) -> bool {
+    matches!(
+        event.kind,
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_))
+    )
+}
+
+/// Returns `true` if the event means a file is complete: it was closed after writing or renamed
+/// to its name. Unlike [`is_write`], a file that is still being downloaded doesn't qualify.
+fn is_finished_write(event: &Event) -> bool {
+    matches!(
+        event.kind,
+        EventKind::Access(AccessKind::Close(AccessMode::Write))
+            | EventKind::Modify(ModifyKind::Name(RenameMode::To))
+    )
+}
+
+/// Returns `true` if the event means the file, e.g. the data file, was written to or replaced.
+fn is_file_change(event: &Event, file: &Path) -> bool {
+    is_write(event)
+        && event
+            .paths
+            .iter()
+            .any(|path| path.file_name() == file.file_name())
+}
+
+/// Directory containing the file, watched instead of the file itself so replacing the file (e.g.
+/// by moving a new archive over the old one) is noticed as well.
+fn directory(file: &str) -> &Path {
+    match Path::new(file).parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    }
+}
+
+fn file_name_starts_with(path: &Path, prefix: &str) -> bool {
+    let name = path.file_name().unwrap_or_default();
+    name.to_string_lossy().starts_with(prefix)
+}
 
-fn reload(state: &SharedState, file: &str) {
-    let mut gc = state.write().unwrap();
-    *gc = ReverseGeocoder::from_file(file);
+fn is_deletes_file(path: &Path) -> bool {
+    file_name_starts_with(path, "deletes-")
+}
+
+/// Returns `true` for GeoNames daily diff files, `modifications-*.txt` and `deletes-*.txt`.
+fn is_diff_file(path: &Path) -> bool {
+    path.extension() == Some("txt".as_ref())
+        && (file_name_starts_with(path, "modifications-") || is_deletes_file(path))
+}
+
+/// Returns the GeoNames daily diff files (`modifications-*.txt` and `deletes-*.txt`) finished by
+/// the event, see [`is_finished_write`]. Each file is therefore applied once, temporary files
+/// with another extension are ignored.
+fn diff_files(event: &Event) -> Vec<&Path> {
+    if !is_finished_write(event) {
+        return vec![];
+    }
+    event
+        .paths
+        .iter()
+        .map(|path| path.as_path())
+        .filter(|path| is_diff_file(path))
+        .collect()
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -56,22 +343,36 @@ async fn main() {
     dump_environment();
 
     tracing::info!("Loading city data and populating tree");
-    let state = Arc::from(RwLock::from(ReverseGeocoder::from_file(&config.data_file)));
+    let (gc, mapped) = load(&config).unwrap_or_else(|e| panic!("Unable to load data file: {}", e));
+    let postal_codes = load_postal_codes(&config)
+        .unwrap_or_else(|e| panic!("Unable to load postal code file: {}", e));
+    let state = Arc::new(AppState {
+        geocoder: RwLock::new(gc),
+        mapped: RwLock::new(mapped),
+        postal_codes: RwLock::new(postal_codes),
+        not_found_if_empty: config.not_found_if_empty,
+    });
 
     // Watch data file for changes
 
     // Create copies to move into watcher fn. Is there any way around this?
-    let df = config.data_file.clone();
+    let my_config = config.clone();
     let my_state = state.clone();
 
     let watcher_fn = move |res: notify::Result<Event>| {
         tracing::debug!("Received watcher event: {:?}", res);
-        match res {
-            Ok(Event {
-                kind: Modify(Data(Content)),
-                ..
-            }) => reload(&my_state, &df),
-            _ => (),
+        if let Ok(event) = res {
+            if is_file_change(&event, Path::new(&my_config.data_file)) {
+                reload(&my_state, &my_config)
+            }
+            if let Some(file) = &my_config.postal_codes_file {
+                if is_file_change(&event, Path::new(file)) {
+                    reload_postal_codes(&my_state, &my_config)
+                }
+            }
+            for diff_file in diff_files(&event) {
+                apply_diff(&my_state, &my_config, diff_file)
+            }
         }
     };
 
@@ -79,9 +380,16 @@ async fn main() {
         notify::recommended_watcher(watcher_fn).expect("Unable to initialize watcher");
 
     if config.watch_for_changes {
-        match watcher.watch(&Path::new(&config.data_file), RecursiveMode::NonRecursive) {
-            Ok(()) => tracing::info!("Watching data file for changes"),
-            Err(e) => tracing::error!("Unable to watch data file: {}", e),
+        let mut directories = vec![directory(&config.data_file)];
+        if let Some(file) = &config.postal_codes_file {
+            directories.push(directory(file));
+        }
+        directories.dedup();
+        for directory in directories {
+            match watcher.watch(directory, RecursiveMode::NonRecursive) {
+                Ok(()) => tracing::info!("Watching {} for changes", directory.display()),
+                Err(e) => tracing::error!("Unable to watch {}: {}", directory.display(), e),
+            }
         }
     }
 
@@ -97,6 +405,12 @@ async fn main() {
     // Configure routes
     let app = Router::new()
         .route("/", get(handlers::geocode))
+        .route("/search", get(handlers::search))
+        .route("/autocomplete", get(handlers::autocomplete))
+        .route("/bbox", get(handlers::bbox))
+        .route("/postalcode", get(handlers::postal_code))
+        .route("/timezone", get(handlers::timezone))
+        .route("/places/:id", get(handlers::place))
         .with_state(state)
         .layer(
             ServiceBuilder::new()
@@ -142,3 +456,168 @@ async fn shutdown_signal() {
 
     tracing::warn!("Shutdown signal received, starting graceful shutdown");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, DataChange};
+
+    #[test]
+    fn detects_changes_of_data_file() {
+        let data_file = Path::new("/data/cities500.zip");
+        let event = |kind, path: &str| Event::new(kind).add_path(path.into());
+
+        let write = EventKind::Modify(ModifyKind::Data(DataChange::Content));
+        let rename = EventKind::Modify(ModifyKind::Name(RenameMode::To));
+        let create = EventKind::Create(CreateKind::File);
+        assert!(is_file_change(
+            &event(write.clone(), "/data/cities500.zip"),
+            data_file
+        ));
+        assert!(is_file_change(
+            &event(rename, "/data/cities500.zip"),
+            data_file
+        ));
+        assert!(is_file_change(
+            &event(create, "/data/cities500.zip"),
+            data_file
+        ));
+
+        assert!(!is_file_change(&event(write, "/data/other.txt"), data_file));
+        let access = EventKind::Access(notify::event::AccessKind::Any);
+        assert!(!is_file_change(
+            &event(access, "/data/cities500.zip"),
+            data_file
+        ));
+    }
+    #[test]
+    fn loads_memory_mapped_data_file() {
+        let file = std::env::temp_dir().join("web-load.mmap");
+        let file = file.to_str().unwrap();
+        ReverseGeocoder::from_file("../cities.txt")
+            .save_mapped(file)
+            .unwrap();
+        let vars =
+            [("GEOCODER_DATA_FILE", file)].map(|(key, value)| (key.to_string(), value.to_string()));
+        let config: Configuration = envy::prefixed("GEOCODER_").from_iter(vars).unwrap();
+
+        let (gc, mapped) = load(&config).unwrap();
+        assert!(gc.cities().is_empty());
+        assert_eq!(mapped.unwrap().len(), 11);
+    }
+
+    #[test]
+    fn loads_postal_codes_with_country_codes_only() {
+        let file = std::env::temp_dir().join("web-postal-codes.txt");
+        std::fs::write(
+            &file,
+            "DE\t41812\tErkelenz\tNordrhein-Westfalen\tNW\t\t\t\t\t51.08\t6.31\t4\n\
+             NL\t6041\tRoermond\tLimburg\tLI\t\t\t\t\t51.19\t5.99\t4\n",
+        )
+        .unwrap();
+        let vars = [
+            ("GEOCODER_POSTAL_CODES_FILE", file.to_str().unwrap()),
+            ("GEOCODER_COUNTRY_CODES", "DE"),
+            ("GEOCODER_MIN_POPULATION", "1000"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        let config: Configuration = envy::prefixed("GEOCODER_").from_iter(vars).unwrap();
+
+        let postal_codes = load_postal_codes(&config).unwrap().unwrap();
+        assert_eq!(postal_codes.cities().len(), 1);
+        assert_eq!(postal_codes.cities()[0].postal_code, "41812");
+    }
+
+    #[test]
+    fn detects_load_options_ignored_by_snapshots() {
+        let config = |vars: &[(&str, &str)]| -> Configuration {
+            let vars = vars
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()));
+            envy::prefixed("GEOCODER_").from_iter(vars).unwrap()
+        };
+
+        let ignored = |key: &str, value: &str| has_row_options(&config(&[(key, value)]));
+        assert!(!has_row_options(&config(&[])));
+        assert!(!ignored("GEOCODER_SKIP_INVALID_ROWS", "true"));
+        assert!(ignored("GEOCODER_MIN_POPULATION", "500"));
+        assert!(ignored("GEOCODER_DROP_DEM", "true"));
+        assert!(ignored("GEOCODER_CSV_COLUMNS", "latitude=1,longitude=2"));
+    }
+
+    #[test]
+    fn applies_diff_files_written_after_data_file() {
+        let dir = std::env::temp_dir().join("web-pending-diffs");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let cities = std::fs::read_to_string("../cities.txt").unwrap();
+        let line = |name: &str| cities.lines().find(|line| line.contains(name)).unwrap();
+        let write = |name: &str, contents: &str| std::fs::write(dir.join(name), contents).unwrap();
+        // Modification times are only as precise as the kernel's clock ticks
+        let pause = || std::thread::sleep(std::time::Duration::from_millis(50));
+
+        write("deletes-2023-06-30.txt", "2929622\tErkelenz\t\n");
+        pause();
+        write("cities.txt", &cities);
+        pause();
+        write(
+            "modifications-2023-07-02.txt",
+            &line("Erkelenz").replace("44650", "2000000"),
+        );
+        write("deletes-2023-07-01.txt", "360630\tCairo\t\n");
+        write(
+            "modifications-2023-07-01.txt",
+            &format!(
+                "{}\n{}\n",
+                line("Erkelenz").replace("44650", "1000000"),
+                line("Cairo")
+            ),
+        );
+        let data_file = dir.join("cities.txt");
+        let vars = [
+            ("GEOCODER_DATA_FILE", data_file.to_str().unwrap()),
+            ("GEOCODER_INSERT_NEW_PLACES", "true"),
+        ]
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+        let config: Configuration = envy::prefixed("GEOCODER_").from_iter(vars).unwrap();
+
+        let (gc, _) = load(&config).unwrap();
+        assert_eq!(gc.get(2929622).unwrap().population, Some(2000000));
+        assert!(gc.get(360630).is_none());
+        assert_eq!(gc.cities().len(), 10);
+    }
+
+    #[test]
+    fn watches_directories_of_files() {
+        assert_eq!(directory("/data/cities500.zip"), Path::new("/data"));
+        assert_eq!(directory("cities500.zip"), Path::new("."));
+    }
+
+    #[test]
+    fn detects_diff_files() {
+        let event = |kind, path: &str| Event::new(kind).add_path(path.into());
+        let closed = EventKind::Access(AccessKind::Close(AccessMode::Write));
+        let renamed = EventKind::Modify(ModifyKind::Name(RenameMode::To));
+
+        let modifications = event(closed.clone(), "/data/modifications-2023-07-01.txt");
+        assert_eq!(
+            diff_files(&modifications),
+            vec![Path::new("/data/modifications-2023-07-01.txt")]
+        );
+        let deletes = event(renamed, "/data/deletes-2023-07-01.txt");
+        assert!(is_deletes_file(diff_files(&deletes)[0]));
+        assert!(diff_files(&event(closed.clone(), "/data/cities500.zip")).is_empty());
+        let part = event(closed, "/data/deletes-2023-07-01.txt.part");
+        assert!(diff_files(&part).is_empty());
+
+        // Still being written
+        let write = EventKind::Modify(ModifyKind::Data(DataChange::Content));
+        let create = EventKind::Create(CreateKind::File);
+        for kind in [write, create] {
+            assert!(diff_files(&event(kind, "/data/deletes-2023-07-01.txt")).is_empty());
+        }
+        // Renamed away, e.g. from a temporary name
+        let renamed_from = EventKind::Modify(ModifyKind::Name(RenameMode::From));
+        assert!(diff_files(&event(renamed_from, "/data/deletes-2023-07-01.txt")).is_empty());
+    }
+}